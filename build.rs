@@ -0,0 +1,33 @@
+// Bakes the git commit and build date into the binary for
+// licensure --version --verbose.
+
+use std::env;
+use std::process::Command;
+
+use chrono::{TimeZone, Utc};
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=LICENSURE_GIT_COMMIT={}", commit);
+
+    // Reproducible builds set SOURCE_DATE_EPOCH to the date to record.
+    let date = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .and_then(|epoch| Utc.timestamp_opt(epoch, 0).single())
+        .unwrap_or_else(Utc::now);
+    println!(
+        "cargo:rustc-env=LICENSURE_BUILD_DATE={}",
+        date.format("%Y-%m-%d")
+    );
+}
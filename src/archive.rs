@@ -0,0 +1,239 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use zip::write::SimpleFileOptions;
+
+/// The archive formats whose entries can be licensed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Format {
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    fn of(path: &str) -> Option<Format> {
+        if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else if path.ends_with(".zip") {
+            Some(Format::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// How an entry is stored, kept so that repackaging the archive
+/// preserves it.
+enum Meta {
+    Tar(Box<tar::Header>),
+    Zip {
+        options: SimpleFileOptions,
+        is_dir: bool,
+        is_symlink: bool,
+    },
+}
+
+/// A single entry of an archive.
+pub struct Entry {
+    /// The path of the entry inside the archive.
+    pub name: String,
+    /// The content of the entry, for links this is the link target.
+    pub data: Vec<u8>,
+    meta: Meta,
+}
+
+impl Entry {
+    /// Whether the entry is a regular file, as opposed to a directory
+    /// or link.
+    pub fn is_file(&self) -> bool {
+        match &self.meta {
+            Meta::Tar(header) => header.entry_type().is_file(),
+            Meta::Zip {
+                is_dir, is_symlink, ..
+            } => !is_dir && !is_symlink,
+        }
+    }
+}
+
+/// An archive read fully into memory.
+pub struct Archive {
+    format: Format,
+    pub entries: Vec<Entry>,
+}
+
+impl Archive {
+    pub fn open(path: &str) -> io::Result<Archive> {
+        let format = Format::of(path)
+            .ok_or_else(|| io::Error::other(format!("{} is not a supported archive", path)))?;
+        let file = File::open(path)?;
+        let entries = match format {
+            Format::TarGz => read_tar_gz(file)?,
+            Format::Zip => read_zip(file)?,
+        };
+
+        Ok(Archive { format, entries })
+    }
+
+    /// Repackage the archive at path. The archive is written next to
+    /// path first so that a failure does not leave a truncated archive
+    /// behind.
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let tmp = format!("{}.licensure-tmp", path);
+        let file = File::create(&tmp)?;
+        let written = match self.format {
+            Format::TarGz => self.write_tar_gz(file),
+            Format::Zip => self.write_zip(file),
+        };
+
+        match written {
+            Ok(()) => fs::rename(&tmp, path),
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                Err(e)
+            }
+        }
+    }
+
+    fn write_tar_gz(&self, file: File) -> io::Result<()> {
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        for entry in &self.entries {
+            let mut header = match &entry.meta {
+                Meta::Tar(header) => (**header).clone(),
+                Meta::Zip { .. } => unreachable!("zip entry in a tar archive"),
+            };
+
+            let kind = header.entry_type();
+            if kind.is_symlink() || kind.is_hard_link() {
+                let target = String::from_utf8_lossy(&entry.data).to_string();
+                builder.append_link(&mut header, &entry.name, target)?;
+            } else {
+                // Licensing changes the size of the entry.
+                header.set_size(entry.data.len() as u64);
+                builder.append_data(&mut header, &entry.name, entry.data.as_slice())?;
+            }
+        }
+
+        builder.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    fn write_zip(&self, file: File) -> io::Result<()> {
+        let mut writer = zip::ZipWriter::new(file);
+        for entry in &self.entries {
+            let (options, is_dir, is_symlink) = match &entry.meta {
+                Meta::Zip {
+                    options,
+                    is_dir,
+                    is_symlink,
+                } => (*options, *is_dir, *is_symlink),
+                Meta::Tar(_) => unreachable!("tar entry in a zip archive"),
+            };
+
+            if is_dir {
+                writer.add_directory(entry.name.as_str(), options)?;
+            } else if is_symlink {
+                let target = String::from_utf8_lossy(&entry.data).to_string();
+                writer.add_symlink(entry.name.as_str(), target, options)?;
+            } else {
+                writer.start_file(entry.name.as_str(), options)?;
+                writer.write_all(&entry.data)?;
+            }
+        }
+
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+fn read_tar_gz(file: File) -> io::Result<Vec<Entry>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut entries = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        let data = match entry.link_name()? {
+            Some(target) => target.to_string_lossy().as_bytes().to_vec(),
+            None => {
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data)?;
+                data
+            }
+        };
+
+        entries.push(Entry {
+            name,
+            data,
+            meta: Meta::Tar(Box::new(entry.header().clone())),
+        });
+    }
+
+    Ok(entries)
+}
+
+fn read_zip(file: File) -> io::Result<Vec<Entry>> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut entries = Vec::with_capacity(archive.len());
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let mut options = SimpleFileOptions::default()
+            .compression_method(entry.compression())
+            .last_modified_time(entry.last_modified().unwrap_or_default());
+        if let Some(mode) = entry.unix_mode() {
+            options = options.unix_permissions(mode);
+        }
+
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        entries.push(Entry {
+            name: entry.name().to_string(),
+            data,
+            meta: Meta::Zip {
+                options,
+                is_dir: entry.is_dir(),
+                is_symlink: entry.is_symlink(),
+            },
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_zip_round_trip() {
+        let dir = TempDir::new("zip");
+        let path = dir.join("archive.zip");
+        let path = path.to_string_lossy().to_string();
+        {
+            let mut writer = zip::ZipWriter::new(File::create(&path).unwrap());
+            writer
+                .add_directory("src", SimpleFileOptions::default())
+                .unwrap();
+            writer
+                .start_file("src/main.py", SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(b"print(1)\n").unwrap();
+            writer.finish().unwrap();
+        }
+
+        let mut archive = Archive::open(&path).unwrap();
+        assert_eq!(2, archive.entries.len());
+        assert!(!archive.entries[0].is_file());
+        assert!(archive.entries[1].is_file());
+
+        archive.entries[1].data = b"# header\nprint(1)\n".to_vec();
+        archive.write(&path).unwrap();
+
+        let archive = Archive::open(&path).unwrap();
+        assert_eq!("src/main.py", archive.entries[1].name);
+        assert_eq!(b"# header\nprint(1)\n".to_vec(), archive.entries[1].data);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+/// The file the cache is kept in, relative to the current working
+/// directory.
+pub const CACHE_FILE: &str = ".licensure-cache";
+
+/// Hash value for the cache, stable between runs of the same build.
+pub fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The modification time and size of a file, which change whenever
+/// its content does.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Stamp {
+    secs: u64,
+    nanos: u32,
+    size: u64,
+}
+
+impl Stamp {
+    /// The stamp of file, None when its metadata cannot be read.
+    pub fn of(file: &str) -> Option<Stamp> {
+        let metadata = fs::metadata(file).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Stamp {
+            secs: modified.as_secs(),
+            nanos: modified.subsec_nanos(),
+            size: metadata.len(),
+        })
+    }
+}
+
+/// A file which had the expected header, along with the hash of that
+/// header so that a change of year or authors invalidates it.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    stamp: Stamp,
+    header: u64,
+}
+
+impl Entry {
+    pub fn new(stamp: Stamp, header: &str) -> Entry {
+        Entry {
+            stamp,
+            header: hash(header),
+        }
+    }
+}
+
+/// The files known to have the expected header, for the config whose
+/// fingerprint is config.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Cache {
+    config: u64,
+    files: HashMap<String, Entry>,
+}
+
+impl Cache {
+    /// Load the cache at path. A missing or unreadable cache, or one
+    /// written for another config, is treated as empty.
+    pub fn load(path: &str, config: u64) -> Cache {
+        let cache = fs::read_to_string(path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<Cache>(&json).ok())
+            .filter(|cache| cache.config == config);
+        match cache {
+            Some(cache) => cache,
+            None => Cache {
+                config,
+                files: HashMap::new(),
+            },
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let json = serde_json::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+
+    /// Whether file is recorded as having the expected header and has
+    /// not changed since.
+    pub fn is_fresh(&self, file: &str, entry: &Entry) -> bool {
+        self.files.get(file) == Some(entry)
+    }
+
+    /// Record entry for file, or forget file when entry is None.
+    pub fn update(&mut self, file: &str, entry: Option<Entry>) {
+        match entry {
+            Some(entry) => self.files.insert(file.to_string(), entry),
+            None => self.files.remove(file),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_load_other_config() {
+        let dir = TempDir::new("cache-file");
+        let path = dir.join("cache.json");
+        let path = path.to_string_lossy().to_string();
+        let entry = Entry::new(Stamp::of("Cargo.toml").unwrap(), "# header\n");
+
+        let mut cache = Cache::load(&path, 1);
+        cache.update("src/main.py", Some(entry));
+        cache.save(&path).unwrap();
+
+        assert!(Cache::load(&path, 1).is_fresh("src/main.py", &entry));
+        assert!(!Cache::load(&path, 2).is_fresh("src/main.py", &entry));
+        assert!(!Cache::load(&path, 1)
+            .is_fresh("src/main.py", &Entry::new(entry.stamp, "# other header\n")));
+    }
+}
@@ -18,6 +18,12 @@ impl BlockComment {
         }
     }
 
+    /// A Javadoc style comment opened with /** on its own line, with
+    /// each line prefixed by * and closed with */.
+    pub fn javadoc() -> BlockComment {
+        BlockComment::new("/**\n", " */\n").with_per_line(" *")
+    }
+
     pub fn set_trailing_lines(mut self, num_lines: usize) -> BlockComment {
         self.trailing_lines = num_lines;
         self
@@ -41,7 +47,7 @@ impl Comment for BlockComment {
             }
             None => new_text.push_str(match columns {
                 Some(cols) => {
-                    wrapped_text = textwrap::fill(text, cols);
+                    wrapped_text = super::wrap(text, cols);
                     wrapped_text.as_str()
                 }
                 None => text,
@@ -0,0 +1,63 @@
+use super::Comment;
+
+/// Brackets the whole header in a single <!-- --> comment as used by
+/// HTML, XML, and similar markup.
+pub struct HtmlComment {
+    indent: usize,
+    trailing_lines: usize,
+}
+
+impl HtmlComment {
+    pub fn new() -> HtmlComment {
+        HtmlComment {
+            indent: 0,
+            trailing_lines: 0,
+        }
+    }
+
+    /// Indent each line of the header inside the comment by the given
+    /// number of spaces.
+    pub fn set_indent(mut self, indent: usize) -> HtmlComment {
+        self.indent = indent;
+        self
+    }
+
+    pub fn set_trailing_lines(mut self, num_lines: usize) -> HtmlComment {
+        self.trailing_lines = num_lines;
+        self
+    }
+}
+
+impl Default for HtmlComment {
+    fn default() -> HtmlComment {
+        HtmlComment::new()
+    }
+}
+
+impl Comment for HtmlComment {
+    fn comment(&self, text: &str, columns: Option<usize>) -> String {
+        let local_copy = match columns {
+            Some(cols) if cols > self.indent => super::wrap(text, cols - self.indent),
+            Some(cols) => super::wrap(text, cols),
+            None => text.to_string(),
+        };
+
+        let indent = " ".repeat(self.indent);
+        let mut new_text = "<!--\n".to_string();
+        for line in local_copy.lines() {
+            if !line.is_empty() {
+                new_text.push_str(&indent);
+                new_text.push_str(line);
+            }
+
+            new_text.push('\n');
+        }
+        new_text.push_str("-->\n");
+
+        for _ in 0..self.trailing_lines {
+            new_text.push('\n');
+        }
+
+        new_text
+    }
+}
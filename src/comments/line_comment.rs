@@ -2,17 +2,36 @@ use super::Comment;
 
 pub struct LineComment {
     character: String,
+    separator: String,
     trailing_lines: usize,
+    case_insensitive: bool,
 }
 
 impl LineComment {
     pub fn new(character: &str) -> LineComment {
         LineComment {
             character: String::from(character),
+            separator: String::from(" "),
             trailing_lines: 0,
+            // Comment characters made of letters are keywords, like the
+            // REM of batch files, which are case insensitive.
+            case_insensitive: !character.is_empty()
+                && character.chars().all(|c| c.is_ascii_alphabetic()),
         }
     }
 
+    /// A Rust doc comment, //! when inner is set and /// otherwise.
+    pub fn rust_doc(inner: bool) -> LineComment {
+        LineComment::new(if inner { "//!" } else { "///" })
+    }
+
+    /// Set what goes between the comment character and the text of
+    /// each line, a single space by default.
+    pub fn set_prefix_separator(mut self, separator: &str) -> LineComment {
+        self.separator = separator.to_string();
+        self
+    }
+
     pub fn set_trailing_lines(mut self, num_lines: usize) -> LineComment {
         self.trailing_lines = num_lines;
         self
@@ -27,9 +46,10 @@ impl LineComment {
 impl Comment for LineComment {
     fn comment(&self, text: &str, columns: Option<usize>) -> String {
         let local_copy = if let Some(cols) = columns {
-            // Subtract two columns to account for the comment
-            // character and space we will add later.
-            textwrap::fill(text, if cols > 2 { cols - 2 } else { cols })
+            // Subtract columns to account for the comment prefix and
+            // separator we will add later.
+            let prefix = self.character.chars().count() + self.separator.chars().count();
+            super::wrap(text, if cols > prefix { cols - prefix } else { cols })
         } else {
             text.to_string()
         };
@@ -45,7 +65,7 @@ impl Comment for LineComment {
         for line in lines {
             let new_line = match line {
                 "" => format!("{}\n", self.character),
-                _ => format!("{} {}\n", self.character, line),
+                _ => format!("{}{}{}\n", self.character, self.separator, line),
             };
 
             new_text.push_str(&new_line);
@@ -57,4 +77,8 @@ impl Comment for LineComment {
 
         new_text
     }
+
+    fn case_insensitive_marker(&self) -> Option<&str> {
+        Some(self.character.as_str()).filter(|_| self.case_insensitive)
+    }
 }
@@ -14,25 +14,86 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 mod block_comment;
+mod html_comment;
 mod line_comment;
+mod padded_comment;
+mod ruled_comment;
 
 pub use block_comment::BlockComment;
+pub use html_comment::HtmlComment;
 pub use line_comment::LineComment;
+pub use padded_comment::PaddedComment;
+pub use ruled_comment::RuledComment;
 
 pub trait Comment {
     fn comment(&self, text: &str, columns: Option<usize>) -> String;
+
+    /// The comment marker which matches in any case at the start of the
+    /// lines of a header, such as the REM of batch files.
+    fn case_insensitive_marker(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Wrap text to width. URLs are never split, neither are words longer
+/// than width, they are left on a line of their own which exceeds
+/// width instead.
+fn wrap(text: &str, width: usize) -> String {
+    let options = textwrap::Options::new(width)
+        .break_words(false)
+        .word_separator(textwrap::WordSeparator::Custom(find_words))
+        .word_splitter(textwrap::WordSplitter::Custom(split_points));
+    textwrap::fill(text, options)
+}
+
+fn is_url(word: &str) -> bool {
+    word.contains("://") || word.trim_start_matches('<').starts_with("www.")
+}
+
+/// Find the words of line like the default word separator, except that
+/// URLs are kept whole rather than broken after each / they contain.
+fn find_words(line: &str) -> Box<dyn Iterator<Item = textwrap::core::Word<'_>> + '_> {
+    let mut words = Vec::new();
+    let mut offset = 0;
+    for word in textwrap::WordSeparator::AsciiSpace.find_words(line) {
+        let len = word.word.len() + word.whitespace.len();
+        if is_url(word.word) {
+            words.push(word);
+        } else {
+            words.extend(
+                textwrap::WordSeparator::UnicodeBreakProperties
+                    .find_words(&line[offset..offset + len]),
+            );
+        }
+        offset += len;
+    }
+
+    Box::new(words.into_iter())
+}
+
+/// Split words at hyphens like the default word splitter, except for
+/// URLs.
+fn split_points(word: &str) -> Vec<usize> {
+    if is_url(word) {
+        Vec::new()
+    } else {
+        textwrap::WordSplitter::HyphenSplitter.split_points(word)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    const EX_TEXT: &'static str = "There once was a man
+    const EX_TEXT: &str = "There once was a man
 with a very nice cat
 the cat wore a top hat
 it looked super dapper
 ";
 
+    const EX_UNWRAPPED_TEXT: &str =
+        "There once was a man with a very nice cat the cat wore a top hat it looked super dapper\n";
+
     #[test]
     fn test_comment_python() {
         assert_eq!(
@@ -61,6 +122,22 @@ it looked super dapper
         )
     }
 
+    #[test]
+    fn test_comment_rust_doc_wrapped() {
+        assert_eq!(
+            "//! There once was a man with a
+//! very nice cat the cat wore a
+//! top hat it looked super dapper
+",
+            LineComment::rust_doc(true).comment(EX_UNWRAPPED_TEXT, Some(34))
+        );
+        assert_eq!(
+            "/// There once was a man
+",
+            LineComment::rust_doc(false).comment("There once was a man", Some(34))
+        );
+    }
+
     #[test]
     fn test_comment_cpp() {
         assert_eq!(
@@ -94,6 +171,23 @@ it looked super dapper
         )
     }
 
+    #[test]
+    fn test_comment_javadoc() {
+        assert_eq!(
+            "/**
+ * There once was a man
+ * with a very nice cat
+ *
+ * the cat wore a top hat
+ */
+",
+            BlockComment::javadoc().comment(
+                "There once was a man\nwith a very nice cat\n\nthe cat wore a top hat\n",
+                None
+            )
+        )
+    }
+
     #[test]
     fn test_comment_html() {
         assert_eq!(
@@ -106,4 +200,95 @@ it looked super dapper
             BlockComment::new("<!--\n", "-->").comment(EX_TEXT, None)
         )
     }
+
+    #[test]
+    fn test_comment_html_indented() {
+        assert_eq!(
+            "<!--
+  There once was a man
+
+  with a very nice cat
+-->
+
+",
+            HtmlComment::new()
+                .set_indent(2)
+                .set_trailing_lines(1)
+                .comment("There once was a man\n\nwith a very nice cat\n", None)
+        )
+    }
+
+    #[test]
+    fn test_comment_block_wrapped() {
+        assert_eq!(
+            "/*
+There once was a man with a
+very nice cat the cat wore
+a top hat it looked super
+dapper
+*/",
+            BlockComment::new("/*\n", "*/").comment(EX_UNWRAPPED_TEXT, Some(28))
+        )
+    }
+
+    #[test]
+    fn test_comment_block_per_line_wrapped() {
+        assert_eq!(
+            "/*
+ * There once was a man with
+ * a very nice cat the cat
+ * wore a top hat it looked
+ * super dapper
+ */",
+            BlockComment::new("/*\n", " */")
+                .with_per_line(" *")
+                .comment(EX_UNWRAPPED_TEXT, Some(28))
+        )
+    }
+
+    #[test]
+    fn test_comment_ruled() {
+        assert_eq!(
+            "# ==========================
+# There once was a man with
+# a very nice cat the cat
+# wore a top hat it looked
+# super dapper
+# --------------------------
+",
+            RuledComment::new(Box::new(LineComment::new("#")))
+                .set_prefix_line(Some("="))
+                .set_suffix_line(Some("-"))
+                .comment(EX_UNWRAPPED_TEXT, Some(28))
+        )
+    }
+
+    #[test]
+    fn test_comment_ruled_unwrapped() {
+        assert_eq!(
+            "/*
+ * ======================
+ * There once was a man
+ * with a very nice cat
+ * the cat wore a top hat
+ * it looked super dapper
+ */",
+            RuledComment::new(Box::new(
+                BlockComment::new("/*\n", " */").with_per_line(" *")
+            ))
+            .set_prefix_line(Some("="))
+            .comment(EX_TEXT, None)
+        )
+    }
+
+    #[test]
+    fn test_wrap_long_url() {
+        assert_eq!(
+            "# You should have\n# received a copy of\n# the license, see\n# <https://www.gnu.org/licenses/>.\n# and/or the well-\n# known notice.\n",
+            LineComment::new("#").comment(
+                "You should have received a copy of the license, see <https://www.gnu.org/licenses/>. and/or the well-known notice.",
+                Some(20)
+            )
+        )
+    }
 }
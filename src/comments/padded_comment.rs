@@ -0,0 +1,33 @@
+use super::Comment;
+
+/// Surrounds the text of the header of another commenter with empty
+/// comment lines, inside the comment and any rule lines.
+pub struct PaddedComment {
+    inner: Box<dyn Comment>,
+    pad_lines: usize,
+}
+
+impl PaddedComment {
+    pub fn new(inner: Box<dyn Comment>, pad_lines: usize) -> PaddedComment {
+        PaddedComment { inner, pad_lines }
+    }
+}
+
+impl Comment for PaddedComment {
+    fn comment(&self, text: &str, columns: Option<usize>) -> String {
+        let body = text.trim_end_matches('\n');
+        let pad = "\n".repeat(self.pad_lines);
+        // The last padding line needs a line ending of its own when the
+        // text has none.
+        let rest = match &text[body.len()..] {
+            "" if self.pad_lines > 0 => "\n",
+            rest => rest,
+        };
+        let padded = format!("{}{}{}{}", pad, body, pad, rest);
+        self.inner.comment(&padded, columns)
+    }
+
+    fn case_insensitive_marker(&self) -> Option<&str> {
+        self.inner.case_insensitive_marker()
+    }
+}
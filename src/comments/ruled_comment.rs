@@ -0,0 +1,87 @@
+use super::Comment;
+
+/// Stands in for a rule line while the header is commented, so that
+/// the rule can then be sized to fit the commented lines.
+const RULE_MARK: char = '\u{e003}';
+
+/// Brackets the header of another commenter with decorative rule
+/// lines, such as a line of = characters, above and below the text.
+pub struct RuledComment {
+    inner: Box<dyn Comment>,
+    prefix_line: Option<String>,
+    suffix_line: Option<String>,
+}
+
+impl RuledComment {
+    pub fn new(inner: Box<dyn Comment>) -> RuledComment {
+        RuledComment {
+            inner,
+            prefix_line: None,
+            suffix_line: None,
+        }
+    }
+
+    pub fn set_prefix_line(mut self, rule: Option<&str>) -> RuledComment {
+        self.prefix_line = rule.map(str::to_string);
+        self
+    }
+
+    pub fn set_suffix_line(mut self, rule: Option<&str>) -> RuledComment {
+        self.suffix_line = rule.map(str::to_string);
+        self
+    }
+}
+
+impl Comment for RuledComment {
+    fn comment(&self, text: &str, columns: Option<usize>) -> String {
+        let body = text.trim_end_matches('\n');
+        let mut marked = String::new();
+        if self.prefix_line.is_some() {
+            marked.push(RULE_MARK);
+            marked.push('\n');
+        }
+        marked.push_str(body);
+        if self.suffix_line.is_some() {
+            marked.push('\n');
+            marked.push(RULE_MARK);
+        }
+        marked.push_str(&text[body.len()..]);
+
+        let commented = self.inner.comment(&marked, columns);
+        // Rules span the configured columns, or the widest line of the
+        // header when it is not wrapped.
+        let width = columns.unwrap_or_else(|| {
+            commented
+                .lines()
+                .filter(|line| !line.contains(RULE_MARK))
+                .map(|line| line.chars().count())
+                .max()
+                .unwrap_or(0)
+        });
+
+        let mut rules = vec![self.prefix_line.as_deref(), self.suffix_line.as_deref()]
+            .into_iter()
+            .flatten();
+        commented
+            .split_inclusive('\n')
+            .map(|line| {
+                if !line.contains(RULE_MARK) {
+                    return line.to_string();
+                }
+
+                let rule = rules.next().unwrap_or_default();
+                let used = line.trim_end_matches('\n').chars().count() - 1;
+                let filled: String = rule
+                    .chars()
+                    .cycle()
+                    .take(width.saturating_sub(used).max(1))
+                    .collect();
+                line.replace(RULE_MARK, &filled)
+            })
+            .collect()
+    }
+
+    fn case_insensitive_marker(&self) -> Option<&str> {
+        self.inner.case_insensitive_marker()
+    }
+}
@@ -1,22 +1,55 @@
+use std::convert::TryFrom;
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::Regex;
 use serde::Deserialize;
 
 use crate::comments::BlockComment;
 use crate::comments::Comment;
+use crate::comments::HtmlComment;
 use crate::comments::LineComment;
+use crate::comments::PaddedComment;
+use crate::comments::RuledComment;
+use crate::editorconfig;
 
 fn def_trailing_lines() -> usize {
     0
 }
 
+fn def_separator() -> String {
+    " ".to_string()
+}
+
 pub fn get_filetype(filename: &str) -> &str {
-    let iter = filename.split('.');
-    match iter.last() {
-        Some(s) => s,
-        None => "",
+    file_name(filename).rsplit('.').next().unwrap_or_default()
+}
+
+/// The last component of the path filename.
+fn file_name(filename: &str) -> &str {
+    filename.rsplit(['/', '\\']).next().unwrap_or_default()
+}
+
+/// The extension of the built-in comment style for the interpreter of
+/// a #! line, such as sh for #!/bin/sh or py for #!/usr/bin/env
+/// python3. None when line is not a shebang or the interpreter is not
+/// known.
+pub fn shebang_extension(line: &str) -> Option<&'static str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = file_name(words.next()?);
+    if interpreter == "env" {
+        // Skip the options of env and the variables it sets.
+        interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
     }
+
+    let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    SHEBANG_INTERPRETERS
+        .iter()
+        .find(|(name, _)| *name == interpreter)
+        .map(|(_, extension)| *extension)
 }
 
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 #[serde(tag = "type")]
 pub enum Commenter {
     #[serde(alias = "block")]
@@ -30,24 +63,133 @@ pub enum Commenter {
     #[serde(alias = "line")]
     Line {
         comment_char: String,
+        /// Put between the comment character and the text of each line.
+        #[serde(default = "def_separator")]
+        separator: String,
+        #[serde(default = "def_trailing_lines")]
+        trailing_lines: usize,
+    },
+    /// A /** */ block with each line prefixed by *.
+    #[serde(alias = "javadoc")]
+    Javadoc {
+        #[serde(default = "def_trailing_lines")]
+        trailing_lines: usize,
+    },
+    /// A single <!-- --> comment around the whole header.
+    #[serde(alias = "html")]
+    Html {
+        #[serde(default)]
+        indent: usize,
         #[serde(default = "def_trailing_lines")]
         trailing_lines: usize,
     },
+    /// Rust doc comments, //! for inner and /// for outer.
+    #[serde(alias = "rust-doc")]
+    RustDoc {
+        #[serde(default)]
+        style: DocStyle,
+        #[serde(default = "def_trailing_lines")]
+        trailing_lines: usize,
+    },
+    /// Formats which cannot hold comments, such as JSON, their files
+    /// are skipped.
+    #[serde(alias = "none")]
+    Uncommentable,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocStyle {
+    #[default]
+    Inner,
+    Outer,
 }
 
 #[derive(Clone, Deserialize)]
 #[serde(untagged)]
-enum FileType {
+enum FileTypeConfig {
     Single(String),
     List(Vec<String>),
 }
 
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Extensions, or filename globs, to which a comment configuration
+/// applies.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "FileTypeConfig")]
+struct FileType {
+    any: bool,
+    extensions: Vec<String>,
+    globs: GlobSet,
+}
+
+impl TryFrom<FileTypeConfig> for FileType {
+    type Error = String;
+
+    fn try_from(cfg: FileTypeConfig) -> Result<FileType, String> {
+        let (any, patterns) = match cfg {
+            FileTypeConfig::Single(ext) => (ext == "any", vec![ext]),
+            FileTypeConfig::List(extensions) => (false, extensions),
+        };
+
+        let mut extensions = Vec::new();
+        let mut globs = GlobSetBuilder::new();
+        for pattern in patterns {
+            if is_glob(&pattern) {
+                let glob = Glob::new(&pattern)
+                    .map_err(|e| format!("invalid extension glob {}: {}", pattern, e))?;
+                globs.add(glob);
+            } else {
+                extensions.push(pattern);
+            }
+        }
+
+        Ok(FileType {
+            any,
+            extensions,
+            globs: globs
+                .build()
+                .map_err(|e| format!("invalid extension glob: {}", e))?,
+        })
+    }
+}
+
 impl FileType {
-    fn matches(&self, ft: &str) -> bool {
-        match self {
-            FileType::Single(ext) => ext == "any" || ext == ft,
-            FileType::List(ref extensions) => extensions.iter().any(|ext| ext == ft),
+    fn from_extensions(extensions: &[&str]) -> FileType {
+        FileType {
+            any: false,
+            extensions: extensions.iter().map(|ext| ext.to_string()).collect(),
+            globs: GlobSet::empty(),
+        }
+    }
+
+    fn matches(&self, filename: &str) -> bool {
+        if self.any {
+            return true;
         }
+
+        // An entry may also be a whole file name, such as Makefile.
+        let ft = get_filetype(filename);
+        let name = file_name(filename);
+        self.extensions.iter().any(|ext| ext == ft || ext == name) || self.globs.is_match(filename)
+    }
+}
+
+/// A regex which is matched against each line of a file.
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
+struct LinePattern(Regex);
+
+impl TryFrom<String> for LinePattern {
+    type Error = String;
+
+    fn try_from(pattern: String) -> Result<LinePattern, String> {
+        Regex::new(&pattern)
+            .map(LinePattern)
+            .map_err(|e| format!("invalid insert_after_pattern {}: {}", pattern, e))
     }
 }
 
@@ -56,32 +198,156 @@ pub struct Config {
     #[serde(alias = "extensions")]
     extension: FileType,
     columns: Option<usize>,
+    trailing_newlines: Option<usize>,
+    insert_after_pattern: Option<LinePattern>,
+    #[serde(default)]
+    insert_before_content: bool,
+    #[serde(default)]
+    insert_after_include_guard: bool,
+    header_prefix_line: Option<String>,
+    header_suffix_line: Option<String>,
+    #[serde(default)]
+    pad_lines: usize,
     commenter: Commenter,
 }
 
 impl Config {
     pub fn default() -> Config {
         Config {
-            extension: FileType::Single("any".to_string()),
+            extension: FileType {
+                any: true,
+                extensions: Vec::new(),
+                globs: GlobSet::empty(),
+            },
             columns: None,
+            trailing_newlines: None,
+            insert_after_pattern: None,
+            insert_before_content: false,
+            insert_after_include_guard: false,
+            header_prefix_line: None,
+            header_suffix_line: None,
+            pad_lines: 0,
             commenter: Commenter::Line {
                 comment_char: "#".to_string(),
+                separator: def_separator(),
                 trailing_lines: 0,
             },
         }
     }
 
-    pub fn matches(&self, file_type: &str) -> bool {
-        self.extension.matches(file_type)
+    /// Return the built-in comment configuration for filename if
+    /// there is one, by its extension or its whole name. These are used
+    /// when no comment configuration from the config file, other than
+    /// "any", matches a file. Templates such as Makefile.in and
+    /// config.h.in are commented like the file they generate.
+    pub fn builtin(filename: &str) -> Option<Config> {
+        let filename = filename.strip_suffix(".in").unwrap_or(filename);
+        BUILTIN_COMMENTERS
+            .iter()
+            .find(|(extensions, _)| {
+                extensions.contains(&get_filetype(filename))
+                    || extensions.contains(&file_name(filename))
+            })
+            .map(|(extensions, commenter)| Config {
+                extension: FileType::from_extensions(extensions),
+                columns: None,
+                trailing_newlines: None,
+                insert_after_pattern: None,
+                insert_before_content: matches!(commenter, Builtin::BeforeContent(_)),
+                insert_after_include_guard: false,
+                header_prefix_line: None,
+                header_suffix_line: None,
+                pad_lines: 0,
+                commenter: commenter.to_commenter(),
+            })
+    }
+
+    pub fn matches(&self, filename: &str) -> bool {
+        self.extension.matches(filename)
+    }
+
+    pub fn is_any(&self) -> bool {
+        self.extension.any
+    }
+
+    /// The kind of commenter and its options, for logging.
+    pub fn get_commenter(&self) -> &Commenter {
+        &self.commenter
+    }
+
+    /// Whether the format of the matched files cannot hold comments, so
+    /// that they should be skipped.
+    pub fn is_uncommentable(&self) -> bool {
+        matches!(self.commenter, Commenter::Uncommentable)
+    }
+
+    /// Indent the per line prefix of a block commenter as the
+    /// .editorconfig for file asks, when the prefix is indented.
+    pub fn with_editorconfig(mut self, file: &str) -> Config {
+        if let Commenter::Block {
+            per_line_char: Some(per_line),
+            ..
+        } = &mut self.commenter
+        {
+            let prefix = per_line.trim_start();
+            if prefix.len() < per_line.len() {
+                if let Some(indent) = editorconfig::indent(Path::new(file)) {
+                    *per_line = format!("{}{}", indent, prefix);
+                }
+            }
+        }
+
+        self
     }
 
+    /// The commenter to apply, uncommentable formats get one which
+    /// leaves the text as is.
     pub fn commenter(&self) -> Box<dyn Comment> {
+        let mut commenter = self.base_commenter();
+        if self.header_prefix_line.is_some() || self.header_suffix_line.is_some() {
+            commenter = Box::new(
+                RuledComment::new(commenter)
+                    .set_prefix_line(self.header_prefix_line.as_deref())
+                    .set_suffix_line(self.header_suffix_line.as_deref()),
+            );
+        }
+
+        if self.pad_lines > 0 {
+            commenter = Box::new(PaddedComment::new(commenter, self.pad_lines));
+        }
+
+        commenter
+    }
+
+    fn base_commenter(&self) -> Box<dyn Comment> {
         match &self.commenter {
+            Commenter::Uncommentable => Box::new(BlockComment::new("", "")),
             Commenter::Line {
                 comment_char,
+                separator,
                 trailing_lines,
             } => Box::new(
-                LineComment::new(comment_char.as_str()).set_trailing_lines(*trailing_lines),
+                LineComment::new(comment_char.as_str())
+                    .set_prefix_separator(separator)
+                    .set_trailing_lines(*trailing_lines),
+            ),
+            Commenter::Javadoc { trailing_lines } => {
+                Box::new(BlockComment::javadoc().set_trailing_lines(*trailing_lines))
+            }
+            Commenter::Html {
+                indent,
+                trailing_lines,
+            } => Box::new(
+                HtmlComment::new()
+                    .set_indent(*indent)
+                    .set_trailing_lines(*trailing_lines),
+            ),
+            Commenter::RustDoc {
+                style,
+                trailing_lines,
+            } => Box::new(
+                LineComment::rust_doc(matches!(style, DocStyle::Inner))
+                    .set_trailing_lines(*trailing_lines),
             ),
             Commenter::Block {
                 start_block_char,
@@ -101,11 +367,201 @@ impl Config {
         }
     }
 
+    /// The width to wrap headers to, None when columns is unset or 0
+    /// which both mean no wrapping.
     pub fn get_columns(&self) -> Option<usize> {
-        self.columns
+        self.columns.filter(|&c| c > 0)
+    }
+
+    /// The byte offset just after the first line of content matching
+    /// insert_after_pattern, which is where the header should go. With
+    /// insert_before_content it is instead the offset of the first line
+    /// which is neither blank nor a comment, and with
+    /// insert_after_include_guard the offset just after the #pragma once
+    /// or #ifndef and #define include guard lines starting content.
+    /// None when none is set, no line matches the pattern, or content
+    /// does not start with an include guard.
+    pub fn insert_position(&self, content: &str) -> Option<usize> {
+        let re = match &self.insert_after_pattern {
+            Some(LinePattern(re)) => re,
+            None if self.insert_after_include_guard => return include_guard_end(content),
+            None if self.insert_before_content => return Some(self.content_start(content)),
+            None => return None,
+        };
+        let mut offset = 0;
+        for line in content.split_inclusive('\n') {
+            offset += line.len();
+            if re.is_match(line.trim_end_matches(['\n', '\r'])) {
+                return Some(offset);
+            }
+        }
+
+        None
+    }
+
+    /// The byte offset of the first line of content which is neither
+    /// blank nor commented with the line comment character, if any.
+    fn content_start(&self, content: &str) -> usize {
+        let comment_char = match &self.commenter {
+            Commenter::Line { comment_char, .. } => Some(comment_char.trim()),
+            _ => None,
+        };
+
+        let mut offset = 0;
+        for line in content.split_inclusive('\n') {
+            let text = line.trim();
+            let is_comment = comment_char.is_some_and(|c| !c.is_empty() && text.starts_with(c));
+            if !text.is_empty() && !is_comment {
+                break;
+            }
+            offset += line.len();
+        }
+
+        offset
+    }
+
+    /// The exact number of blank lines to leave between the header and
+    /// the rest of the file, None to keep whatever follows the header.
+    pub fn get_trailing_newlines(&self) -> Option<usize> {
+        self.trailing_newlines
+    }
+}
+
+/// A static description of a built-in commenter.
+enum Builtin {
+    Line(&'static str),
+    Block(&'static str, &'static str, Option<&'static str>),
+    Html,
+    /// A line comment whose header goes after any leading blank lines
+    /// and comments, for config formats.
+    BeforeContent(&'static str),
+    Uncommentable,
+}
+
+impl Builtin {
+    fn to_commenter(&self) -> Commenter {
+        match self {
+            Builtin::Line(comment_char) | Builtin::BeforeContent(comment_char) => Commenter::Line {
+                comment_char: comment_char.to_string(),
+                separator: def_separator(),
+                trailing_lines: 0,
+            },
+            Builtin::Block(start, end, per_line) => Commenter::Block {
+                start_block_char: start.to_string(),
+                end_block_char: end.to_string(),
+                per_line_char: per_line.map(str::to_string),
+                trailing_lines: 0,
+            },
+            Builtin::Html => Commenter::Html {
+                indent: 0,
+                trailing_lines: 0,
+            },
+            Builtin::Uncommentable => Commenter::Uncommentable,
+        }
     }
 }
 
+const BUILTIN_COMMENTERS: &[(&[&str], Builtin)] = &[
+    (
+        &[
+            "c", "h", "cc", "cpp", "cxx", "hh", "hpp", "cs", "d", "dart", "go", "groovy", "java",
+            "js", "json5", "jsonc", "jsx", "kt", "kts", "mjs", "php", "proto", "rs", "scala",
+            "scss", "swift", "ts", "tsx",
+        ],
+        Builtin::Line("//"),
+    ),
+    (&["css"], Builtin::Block("/*\n", " */\n", Some(" *"))),
+    (&["fs", "ml", "mli"], Builtin::Block("(*\n", "*)\n", None)),
+    (
+        &[
+            "bash", "cmake", "coffee", "cr", "ex", "exs", "fish", "jl", "mk", "nim", "pl", "pm",
+            "ps1", "psd1", "psm1", "py", "r", "rb", "sh", "tcl", "yaml", "yml", "zsh",
+        ],
+        Builtin::Line("#"),
+    ),
+    (&["ini", "toml"], Builtin::BeforeContent("#")),
+    (&["bat", "cmd"], Builtin::Line("REM")),
+    (&["clj", "cljs", "el", "lisp", "scm"], Builtin::Line(";;")),
+    (&["hs", "lua", "sql"], Builtin::Line("--")),
+    (
+        &[
+            "htm", "html", "markdown", "md", "svg", "vue", "xhtml", "xml",
+        ],
+        Builtin::Html,
+    ),
+    (&["json"], Builtin::Uncommentable),
+    // Files known by their whole name rather than an extension.
+    (
+        &[
+            "CMakeLists.txt",
+            "Containerfile",
+            "Dockerfile",
+            "GNUmakefile",
+            "Gemfile",
+            "Makefile",
+            "Rakefile",
+            "Vagrantfile",
+            "makefile",
+        ],
+        Builtin::Line("#"),
+    ),
+    (&["Jenkinsfile"], Builtin::Line("//")),
+];
+
+/// The interpreters of #! lines, without any version suffix, along
+/// with the extension of their built-in comment style.
+const SHEBANG_INTERPRETERS: &[(&str, &str)] = &[
+    ("Rscript", "r"),
+    ("bash", "bash"),
+    ("crystal", "cr"),
+    ("dash", "sh"),
+    ("deno", "ts"),
+    ("elixir", "exs"),
+    ("fish", "fish"),
+    ("julia", "jl"),
+    ("ksh", "sh"),
+    ("lua", "lua"),
+    ("node", "js"),
+    ("perl", "pl"),
+    ("php", "php"),
+    ("pwsh", "ps1"),
+    ("python", "py"),
+    ("ruby", "rb"),
+    ("sh", "sh"),
+    ("tclsh", "tcl"),
+    ("zsh", "zsh"),
+];
+
+/// The byte offset just after the include guard of a C or C++ header,
+/// either a #pragma once line or an #ifndef NAME line followed by
+/// #define NAME, when it is the first thing in content other than
+/// blank lines.
+fn include_guard_end(content: &str) -> Option<usize> {
+    let pragma = Regex::new(r"^\s*#\s*pragma\s+once\b").unwrap();
+    let ifndef = Regex::new(r"^\s*#\s*ifndef\s+(\w+)").unwrap();
+    let define = Regex::new(r"^\s*#\s*define\s+(\w+)").unwrap();
+
+    let mut offset = 0;
+    let mut guard = None;
+    for line in content.split_inclusive('\n') {
+        offset += line.len();
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match guard {
+            None if pragma.is_match(line) => return Some(offset),
+            None => guard = Some(ifndef.captures(line)?[1].to_string()),
+            Some(name) => {
+                let defined = define.captures(line)?;
+                return (defined[1] == name).then_some(offset);
+            }
+        }
+    }
+
+    None
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -115,4 +571,253 @@ pub mod tests {
         assert_eq!("py", get_filetype("test.py"))
     }
 
+    #[test]
+    fn test_file_name_commenters() {
+        let comment = |file| Config::builtin(file).map(|cfg| cfg.commenter().comment("text", None));
+        for file in [
+            "Makefile",
+            "src/Makefile",
+            "Makefile.in",
+            "docker/Dockerfile",
+        ] {
+            assert_eq!(Some("# text\n".to_string()), comment(file), "{}", file);
+        }
+        assert_eq!(Some("# text\n".to_string()), comment("CMakeLists.txt"));
+        assert_eq!(Some("// text\n".to_string()), comment("config.h.in"));
+        assert_eq!(None, comment("notes.txt"));
+        assert_eq!(None, comment("README"));
+
+        let cfg: Config = serde_yaml::from_str(
+            "extensions: [Dockerfile]\ncommenter: {type: line, comment_char: '#'}",
+        )
+        .unwrap();
+        assert!(cfg.matches("images/base/Dockerfile"));
+        assert!(!cfg.matches("images/base/Dockerfile.md"));
+    }
+
+    #[test]
+    fn test_shebang_extension() {
+        assert_eq!(Some("sh"), shebang_extension("#!/bin/sh"));
+        assert_eq!(Some("py"), shebang_extension("#!/usr/bin/env python3"));
+        assert_eq!(Some("py"), shebang_extension("#! /usr/bin/python3.11 -u"));
+        assert_eq!(
+            Some("js"),
+            shebang_extension("#!/usr/bin/env -S NODE_ENV=production node --inspect")
+        );
+        assert_eq!(None, shebang_extension("#!/usr/bin/env unknown"));
+        assert_eq!(None, shebang_extension("# not a shebang"));
+    }
+
+    #[test]
+    fn test_glob_extension() {
+        let cfg: Config = serde_yaml::from_str(
+            "extensions: [\"*.pb.go\", proto]\ncommenter: {type: line, comment_char: \"//\"}",
+        )
+        .unwrap();
+        assert!(cfg.matches("api/service.pb.go"));
+        assert!(cfg.matches("api/service.proto"));
+        assert!(!cfg.matches("api/service.go"));
+    }
+
+    #[test]
+    fn test_uncommentable_commenter() {
+        let cfg: Config =
+            serde_yaml::from_str("extension: ipynb\ncommenter: {type: none}").unwrap();
+        assert!(cfg.is_uncommentable());
+    }
+
+    #[test]
+    fn test_line_separator() {
+        let cfg: Config = serde_yaml::from_str(
+            "extension: py\ncommenter: {type: line, comment_char: \"#\", separator: \"\"}",
+        )
+        .unwrap();
+        assert_eq!("#text\n", cfg.commenter().comment("text", None));
+
+        let cfg: Config =
+            serde_yaml::from_str("extension: py\ncommenter: {type: line, comment_char: \"#\"}")
+                .unwrap();
+        assert_eq!("# text\n", cfg.commenter().comment("text", None));
+    }
+
+    #[test]
+    fn test_javadoc_commenter() {
+        let cfg: Config =
+            serde_yaml::from_str("extension: java\ncommenter: {type: javadoc}").unwrap();
+        assert!(cfg.matches("src/Main.java"));
+        assert_eq!("/**\n * text\n */\n", cfg.commenter().comment("text", None));
+    }
+
+    #[test]
+    fn test_pad_lines() {
+        let cfg: Config = serde_yaml::from_str(
+            "extension: py\npad_lines: 1\ncommenter: {type: line, comment_char: '#'}",
+        )
+        .unwrap();
+        assert_eq!("#\n# text\n#\n", cfg.commenter().comment("text\n", None));
+        assert_eq!("#\n# text\n#\n", cfg.commenter().comment("text", None));
+
+        let cfg: Config = serde_yaml::from_str(
+            "extension: c\npad_lines: 2\nheader_prefix_line: '='\ncommenter: {type: block, start_block_char: \"/*\\n\", end_block_char: \" */\\n\", per_line_char: \" *\"}",
+        )
+        .unwrap();
+        assert_eq!(
+            "/*\n * ====\n *\n *\n * text\n *\n *\n */\n",
+            cfg.commenter().comment("text\n", None)
+        );
+    }
+
+    #[test]
+    fn test_block_with_editorconfig() {
+        let dir = crate::testing::TempDir::new("block-editorconfig");
+        dir.write(".editorconfig", "root = true\n[*.c]\nindent_style = tab\n");
+        let file = dir.join("main.c").to_string_lossy().to_string();
+        let other = dir.join("main.h").to_string_lossy().to_string();
+
+        let cfg: Config = serde_yaml::from_str(
+            "extension: any\ncommenter: {type: block, start_block_char: \"/*\\n\", end_block_char: \" */\\n\", per_line_char: \" *\"}",
+        )
+        .unwrap();
+        assert_eq!(
+            "/*\n\t* text\n */\n",
+            cfg.clone()
+                .with_editorconfig(&file)
+                .commenter()
+                .comment("text", None)
+        );
+        assert_eq!(
+            "/*\n * text\n */\n",
+            cfg.with_editorconfig(&other)
+                .commenter()
+                .comment("text", None)
+        );
+    }
+
+    #[test]
+    fn test_rust_doc_commenter() {
+        let cfg: Config =
+            serde_yaml::from_str("extension: rs\ncommenter: {type: rust-doc}").unwrap();
+        assert_eq!("//! text\n", cfg.commenter().comment("text", None));
+
+        let cfg: Config =
+            serde_yaml::from_str("extension: rs\ncommenter: {type: rust-doc, style: outer}")
+                .unwrap();
+        assert_eq!("/// text\n", cfg.commenter().comment("text", None));
+    }
+
+    #[test]
+    fn test_columns() {
+        let cfg: Config = serde_yaml::from_str(
+            "extension: c\ncolumns: 20\ncommenter: {type: line, comment_char: \"//\"}",
+        )
+        .unwrap();
+        assert_eq!(Some(20), cfg.get_columns());
+        let header = cfg.commenter().comment(
+            "Copyright 2020 Mathew Robinson, licensed under the MIT license.",
+            cfg.get_columns(),
+        );
+        assert_eq!(
+            "// Copyright 2020\n// Mathew Robinson,\n// licensed under\n// the MIT license.\n",
+            header
+        );
+        assert!(header.lines().all(|l| l.len() <= 20));
+
+        let cfg: Config = serde_yaml::from_str(
+            "extension: c\ncolumns: 0\ncommenter: {type: line, comment_char: \"//\"}",
+        )
+        .unwrap();
+        assert_eq!(None, cfg.get_columns());
+    }
+
+    #[test]
+    fn test_insert_position() {
+        let cfg: Config = serde_yaml::from_str(
+            "extension: go\ninsert_after_pattern: \"^package \"\ncommenter: {type: line, comment_char: \"//\"}",
+        )
+        .unwrap();
+        assert_eq!(
+            Some(29),
+            cfg.insert_position("// +build linux\npackage main\n\nfunc main() {}\n")
+        );
+        assert_eq!(None, cfg.insert_position("func main() {}\n"));
+        assert_eq!(None, Config::default().insert_position("package main\n"));
+    }
+
+    #[test]
+    fn test_insert_after_include_guard() {
+        let cfg: Config = serde_yaml::from_str(
+            "extension: h\ninsert_after_include_guard: true\ncommenter: {type: line, comment_char: \"//\"}",
+        )
+        .unwrap();
+        assert_eq!(
+            Some(14),
+            cfg.insert_position("\n#pragma once\n\nint f();\n")
+        );
+        assert_eq!(
+            Some(34),
+            cfg.insert_position("#ifndef PARSER_H\n#define PARSER_H\n\nint f();\n#endif\n")
+        );
+        assert_eq!(
+            None,
+            cfg.insert_position("#ifndef PARSER_H\n#define OTHER_H\n")
+        );
+        assert_eq!(
+            None,
+            cfg.insert_position("#include <stdio.h>\n#pragma once\n")
+        );
+    }
+
+    #[test]
+    fn test_insert_before_content() {
+        let cfg = Config::builtin("pyproject.toml").unwrap();
+        assert_eq!("# text\n", cfg.commenter().comment("text", None));
+        assert_eq!(Some(0), cfg.insert_position("[tool]\nkey = 1\n"));
+        assert_eq!(Some(15), cfg.insert_position("\n# Tool config\n[tool]\n"));
+        assert_eq!(
+            Some(3),
+            Config::builtin("setup.ini")
+                .unwrap()
+                .insert_position("\n\r\nkey = 1\n")
+        );
+    }
+
+    #[test]
+    fn test_builtin() {
+        assert!(Config::builtin("src/main.rs").is_some());
+        assert!(Config::builtin("script.py").is_some());
+        assert!(Config::builtin("icon.svg").is_some());
+        assert_eq!(
+            "-- text\n",
+            Config::builtin("init.lua")
+                .unwrap()
+                .commenter()
+                .comment("text", None)
+        );
+        assert_eq!(
+            "(*\ntext\n*)\n",
+            Config::builtin("lib/parser.mli")
+                .unwrap()
+                .commenter()
+                .comment("text\n", None)
+        );
+        assert_eq!(
+            "REM text\n",
+            Config::builtin("build.bat")
+                .unwrap()
+                .commenter()
+                .comment("text", None)
+        );
+        assert_eq!(
+            "# text\n",
+            Config::builtin("profile.ps1")
+                .unwrap()
+                .commenter()
+                .comment("text", None)
+        );
+        assert!(Config::builtin("unknown.xyz").is_none());
+        assert!(Config::builtin("package.json").unwrap().is_uncommentable());
+        assert!(!Config::builtin("tsconfig.jsonc")
+            .unwrap()
+            .is_uncommentable());
+    }
 }
@@ -1,23 +1,77 @@
-// Simply contains the default YAML config for generation and consumption
+// Simply contains the default YAML and TOML configs for generation and consumption
 pub const DEFAULT_CONFIG: &str = r##"
+# The line ending used for inserted headers, one of lf, crlf, or auto
+# which uses the line ending most common in each file.
+# line_ending: auto
+#
+# When true files which have a Copyright or SPDX-License-Identifier
+# line in their first copyright_search_lines lines are skipped even if
+# it is not our license header, such as vendored files.
+# skip_if_any_copyright: true
+# copyright_search_lines: 10
+#
+# Files with opt_out_marker in their first copyright_search_lines lines,
+# in a comment of any style, are never licensed, such as generated files.
+# opt_out_marker: licensure:disable
+#
+# Lines at the top of a file matching one of modeline_patterns are kept
+# above the header, like a shebang. By default these are Emacs
+# -*- mode: python -*- lines and Vim modelines such as
+# "# vim: set ft=python:".
+# modeline_patterns:
+#   - -\*-.*-\*-
+#   - \s(?:vi|vim|ex):
+#
+# Custom tokens which can be referenced from license templates, each
+# [name] is replaced with its value. Built-in tokens such as [year]
+# cannot be redefined.
+# tokens:
+#   department: Legal
+#   contact: legal@example.com
+#
+# Information about the project which can be referenced from license
+# templates. The [project] token is replaced with project.name.
+# [vcs_url] is replaced with the https URL of the origin git remote,
+# when there is none it is an error unless vcs_url_optional is set.
+# project:
+#   name: Your Project
+#   vcs_url_optional: true
+#
+# The URLs the [license_url] token is replaced with by license ident,
+# common licenses such as MIT and Apache-2.0 have built-in ones.
+# license_urls:
+#   LicenseRef-Acme: https://acme.example.com/LICENSE
+#
 # Regexes which if matched by a file path will always be excluded from
 # getting a license header
 excludes:
   - \.gitignore
   - .*lock
   - \.git/.*
-  - \.licensure\.yml
+  - \.licensure\.(yml|toml)
   - README.*
   - LICENSE.*
   - .*\.(md|rst|txt)
+#
+# Regexes of the only file paths to license, when given any other file
+# is skipped. Excludes still apply to the included files.
+# includes:
+#   - src/.*
+#
 # Definition of the licenses used on this project and to what files
 # they should apply.
 #
 # No default license configuration is provided. This section must be
 # configured by the user.
+#
+# Licenses are checked from top to bottom and the first one whose
+# files match is used, so more specific entries such as vendor/
+# directories should come before catch-all ones. Files which match no
+# license are skipped and reported.
 licenses:
-  # Either a regex or the string "any" to determine to what files this
-  # license should apply. It is common for projects to have files
+  # Either a regex, a glob prefixed with "glob:" such as
+  # "glob:vendor/**", or the string "any" to determine to what files
+  # this license should apply. It is common for projects to have files
   # under multiple licenses or with multiple copyright holders. This
   # provides the ability to automatically license files correctly
   # based on their file paths.
@@ -31,13 +85,96 @@ licenses:
   #   true.
   #   ident: MIT
   #
+  #   The SPDX identifier substituted for [spdx], defaults to ident.
+  #   spdx_id: MIT
+  #
   #   A list of authors who hold copyright over these files
   #   authors:
   #       Provide either your full name or company name for copyright purposes
   #     - name: Your Name Here
-  #       Optionally provide email for copyright purposes
+  #       Optionally provide email for copyright purposes, or a list
+  #       of emails, e.g. [you@yourdomain.com, you@work.com]
   #       email: you@yourdomain.com
+  #       Optionally provide an ORCID iD and a role
+  #       orcid: 0000-0002-1825-0097
+  #       role: Maintainer
+  #
+  #   Instead of a list authors may be "from-git" to use the authors
+  #   of each file from git history, most frequent committer first.
+  #   Files without history use the authors of the whole repository.
+  #   exclude_authors drops authors by name or email, e.g. bots.
+  #   authors: from-git
+  #   exclude_authors:
+  #     - dependabot[bot]
+  #
+  #   Instead of authors, authors_file reads them from a file such as
+  #   AUTHORS, relative to the directory of this config file, with one
+  #   "Name <email>" per line. Blank and # lines are ignored.
+  #   authors_file: AUTHORS
+  #
+  #   Authors are listed in their configured order, or with from-git
+  #   by number of commits, unless sort_authors lists them by name.
+  #   sort_authors: true
+  #
+  #   List at most max_authors authors, followed by et_al ("et al." by
+  #   default) when there are more. 0, the default, lists every author.
+  #   max_authors: 3
+  #   et_al: and contributors
+  #
+  #   Text put before and after the authors, separated by a space,
+  #   which stays the same when the authors change.
+  #   authors_suffix: and contributors
+  #
+  #   The fields of each author to render, in order, out of name,
+  #   email, orcid, and role. By default every field an author has is
+  #   rendered, e.g. Name <email> (ORCID: 0000-0002-1825-0097, Maintainer).
+  #   author_fields: [name, orcid]
+  #
+  #   Instead of authors a list of copyrights, each with its own
+  #   authors and optionally its own year, renders the line of the
+  #   template with [name of author] once per copyright, e.g.
+  #     Copyright 2015-2020 Acme Inc.
+  #     Copyright 2024 Acme Inc. contributors
+  #   copyrights:
+  #     - authors:
+  #         - name: Acme Inc.
+  #       year: 2015-2020
+  #     - authors:
+  #         - name: Acme Inc. contributors
   # 
+  #   Setting type to spdx-short replaces the template with a copyright
+  #   line followed by an SPDX-License-Identifier tag for ident, e.g.
+  #     Copyright 2024 Your Name Here
+  #     SPDX-License-Identifier: MIT
+  #   type: spdx-short
+  #
+  #   Setting type to reuse renders a REUSE header, with the year and
+  #   authors in an SPDX-FileCopyrightText tag, and leaves files which
+  #   already have another REUSE header as they are.
+  #   type: reuse
+  #
+  #   Instead of template the copyright line and the license notice
+  #   after it may be set separately, each defaulting to the line
+  #   spdx-short uses.
+  #   copyright_template: Copyright [copyright_symbol] [year] [name of author]
+  #   notice_template: "SPDX-License-Identifier: [spdx]"
+  #
+  #   Render [year] as a range from start_year to the current year,
+  #   e.g. 2018-2024. If start_year is omitted, or equal to the current
+  #   year, only the current year is rendered.
+  #   year_range: true
+  #   start_year: 2018
+  #
+  #   Use the year each file was first committed to git as its year,
+  #   or as the start of the range when year_range is set. Files
+  #   without git history fall back to the current year.
+  #   use_git_year: true
+  #
+  #   Setting include_year to false leaves the year out of the header,
+  #   the [year] token is removed along with the space before it.
+  #   Headers which still have a year are considered outdated.
+  #   include_year: false
+  #
   #   The template that will be rendered to generate the header before
   #   comment characters are applied. Available variables are:
   #    - [year]: substituted with the current year.
@@ -45,11 +182,29 @@ licenses:
   #      if provided. If email is provided the output appears as Full
   #      Name <email@example.com>. If multiple authors are provided the
   #      list is concatenated together with commas.
+  #    - [project]: Substituted with project.name, which must be set
+  #      when the template uses it.
+  #    - [vcs_url]: Substituted with the https URL of the origin remote.
+  #    - [license_url]: Substituted with the URL of the license text.
+  #    - [env:NAME]: Substituted with the environment variable NAME,
+  #      which must be set unless a default is given as
+  #      [env:NAME:default].
+  #    - [copyright_symbol]: Substituted with copyright_symbol, either
+  #      (C), the default, or ©. Headers with either symbol are
+  #      recognized when switching between them.
+  #    - [spdx]: Substituted with spdx_id, or ident when it is unset.
+  #    - [if ext == EXT]...[endif]: Only kept for files whose extension
+  #      is EXT or ends with .EXT, e.g. [if ext == go] for api.pb.go.
+  #   copyright_symbol: ©
   #   template: |
   #     Copyright [year] [name of author]. All rights reserved. Use of
   #     this source code is governed by the [ident] license that can be
   #     found in the LICENSE file.
   #
+  #   Instead of template, template_file reads the template from a
+  #   UTF-8 file, relative to the directory of this config file.
+  #   template_file: LICENSE_HEADER.txt
+  #
   #   If auto_template is true then template is ignored and the SPDX
   #   API will be queried with the ident value to automatically
   #   determine the license header template. auto_template works best
@@ -64,14 +219,36 @@ licenses:
   #
   #   Important Note: this means the ident must be a valid SPDX identifier
   #   auto_template: true
+  #
+  #   Downloaded SPDX license info is cached in
+  #   $XDG_CACHE_HOME/licensure/spdx (~/.cache/licensure/spdx by
+  #   default) and reused from there, so auto_template keeps working
+  #   offline. spdx_cache_dir overrides the cache location. No SPDX
+  #   license info ships with Licensure, for reproducible CI point it
+  #   at a directory committed to your repository, run once online to
+  #   fill it, commit it, and set spdx_offline to never download.
+  #   spdx_cache_dir: .licensure/spdx
+  #   spdx_offline: true
   # 
   #   Try to detect the text wrapping of the template, and unwrap it
   #   unwrap_text: true
+  #
+  #   Keep the line breaks of the template exactly as written instead,
+  #   neither unwrapping it nor rewrapping it to the columns
+  #   preserve_line_breaks: true
 
 # Define type of comment characters to apply based on file extensions.
+#
+# Licensure has built-in comment styles for many common languages
+# which are used for files that none of the configurations below
+# match, so this section may be omitted entirely. They also know files
+# by name, such as Makefile, and files without any are commented in the
+# style of the language of their shebang, such as #!/bin/sh.
 comments:
   # The extensions (or singular extension) field defines which file
-  # extensions to apply the commenter to.
+  # extensions to apply the commenter to. Entries containing glob
+  # characters such as "*.pb.go" are matched against the file name
+  # instead, and whole file names such as Dockerfile match too.
   - extensions:
       - js
       - rs
@@ -84,7 +261,42 @@ comments:
     # each line in the license header. It will then apply a number of
     # empty newlines to the end of the header equal to trailing_lines.
     #
-    # If trailing_lines is omitted it is assumed to be 0.
+    # If trailing_lines is omitted it is assumed to be 0. Updating an
+    # outdated header tops the lines after it up to trailing_lines.
+    #
+    # separator is put between the comment_char and the text of each
+    # line, it is a single space when omitted.
+    # separator: ""
+    #
+    # Setting trailing_newlines, alongside extensions, makes sure that
+    # exactly that many blank lines separate the header from the rest
+    # of the file, both when adding and when updating headers.
+    # trailing_newlines: 1
+    #
+    # insert_after_pattern is a regex, when a line of a file matches it
+    # the header is inserted after the first such line instead of at the
+    # top of the file, e.g. after the package clause in Go.
+    # insert_after_pattern: "^package "
+    #
+    # Setting insert_before_content inserts the header after any leading
+    # blank lines and comments, before the first line of content. The
+    # built-in comments for .toml and .ini files do this.
+    # insert_before_content: true
+    #
+    # Setting insert_after_include_guard inserts the header of C and C++
+    # headers after their #pragma once line or #ifndef and #define
+    # include guard lines.
+    # insert_after_include_guard: true
+    #
+    # header_prefix_line and header_suffix_line put a rule made of the
+    # given characters above and below the header text, repeated to the
+    # columns width or to the widest line of the header.
+    # header_prefix_line: "="
+    # header_suffix_line: "="
+    #
+    # pad_lines puts that many empty comment lines above and below the
+    # header text, inside the comment and any rule lines.
+    # pad_lines: 1
     commenter:
       type: line
       comment_char: "//"
@@ -98,7 +310,9 @@ comments:
     # in the license header and add end_block_char as the last character
     # in the license header. If per_line_char is provided each line of
     # the header between the block start and end characters will be
-    # line commented with the per_line_char
+    # line commented with the per_line_char. An indented per_line_char
+    # such as " *" follows the indent_style of the .editorconfig of
+    # the file when it has one.
     #
     # trailing_lines works the same for both block and line commenter
     # types
@@ -122,13 +336,14 @@ comments:
       comment_char: ";;;"
       trailing_lines: 0
   # The extension string "any" is special and so will match any file
-  # extensions. Commenter configurations are always checked in the
-  # order they are defined, so if any is used it should be the last
-  # commenter configuration or else it will override all others.
+  # extensions. Commenter configurations are checked in the order
+  # they are defined, "any" included, and the built-in comment styles
+  # are only used when none of them match.
   #
   # In this configuration if we can't match the file extension we fall
   # back to the popular "#" line comment used in most scripting
-  # languages.
+  # languages. Remove it to use the built-in comment styles for the
+  # other files instead.
   - extension: any
     commenter:
       type: line
@@ -136,3 +351,86 @@ comments:
       trailing_lines: 0
     
 "##;
+
+pub const DEFAULT_CONFIG_TOML: &str = r##"
+# Regexes which if matched by a file path will always be excluded from
+# getting a license header
+excludes = [
+  '\.gitignore',
+  '.*lock',
+  '\.git/.*',
+  '\.licensure\.(yml|toml)',
+  'README.*',
+  'LICENSE.*',
+  '.*\.(md|rst|txt)',
+]
+
+# Regexes of the only file paths to license, when given any other file
+# is skipped. Excludes still apply to the included files.
+# includes = ['src/.*']
+
+# Definition of the licenses used on this project and to what files
+# they should apply. The available options are the same as for the
+# YAML config, see licensure --generate-config for their full
+# documentation.
+#
+# No default license configuration is provided. This section must be
+# configured by the user.
+#
+# Licenses are checked from top to bottom and the first one whose
+# files match is used, so more specific entries such as vendor/
+# directories should come before catch-all ones. Files which match no
+# license are skipped and reported.
+#
+# [[licenses]]
+# files = "any"
+# ident = "MIT"
+# authors = [{ name = "Your Name Here", email = "you@yourdomain.com" }]
+# template = """
+# Copyright [year] [name of author]. All rights reserved. Use of
+# this source code is governed by the [ident] license that can be
+# found in the LICENSE file.
+# """
+
+# Define type of comment characters to apply based on file extensions.
+#
+# Licensure has built-in comment styles for many common languages
+# which are used for files that none of the configurations below
+# match, so this section may be omitted entirely. They also know files
+# by name, such as Makefile, and files without any are commented in the
+# style of the language of their shebang, such as #!/bin/sh.
+#
+# The extensions (or singular extension) field defines which file
+# extensions to apply the commenter to. Entries containing glob
+# characters such as "*.pb.go" are matched against the file name
+# instead, and whole file names such as Dockerfile match too.
+#
+# The commenter table defines the kind of commenter to generate, see
+# licensure --generate-config for a description of line and block
+# commenters.
+[[comments]]
+extensions = ["js", "rs", "go"]
+commenter = { type = "line", comment_char = "//", trailing_lines = 0 }
+
+[[comments]]
+extensions = ["css", "cpp", "c"]
+commenter = { type = "block", start_block_char = "/*\n", end_block_char = "*/", per_line_char = "*", trailing_lines = 0 }
+
+# In this case extension is singular and a single string extension is provided.
+[[comments]]
+extension = "html"
+commenter = { type = "block", start_block_char = "<!--\n", end_block_char = "-->" }
+
+[[comments]]
+extensions = ["el", "lisp"]
+commenter = { type = "line", comment_char = ";;;", trailing_lines = 0 }
+
+# The extension string "any" is special and so will match any file
+# extensions. Commenter configurations are checked in the order they
+# are defined, "any" included, and the built-in comment styles are only
+# used when none of them match. Remove it to use the built-in comment
+# styles for the other files instead.
+[[comments]]
+extension = "any"
+commenter = { type = "line", comment_char = "#", trailing_lines = 0 }
+"##;
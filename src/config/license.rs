@@ -1,15 +1,26 @@
-use std::process;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
+use globset::{Glob, GlobMatcher};
 use regex::Regex;
 use serde::Deserialize;
 
-use crate::template::{Authors, Context, Template};
+use crate::config::xdg_cache_dir;
+use crate::git;
+use crate::template::{
+    compose_template, AuthorField, Authors, Context, Copyright, Template,
+    DEFAULT_COPYRIGHT_TEMPLATE, DEFAULT_NOTICE_TEMPLATE, DEFAULT_REUSE_COPYRIGHT_TEMPLATE,
+};
 
 #[derive(Deserialize)]
-#[serde(from = "String")]
+#[serde(try_from = "String")]
 struct FileMatcher {
     any: bool,
     regex: Option<Regex>,
+    glob: Option<GlobMatcher>,
 }
 
 impl FileMatcher {
@@ -18,6 +29,10 @@ impl FileMatcher {
             return true;
         }
 
+        if let Some(g) = &self.glob {
+            return g.is_match(s);
+        }
+
         match &self.regex {
             Some(r) => r.is_match(s),
             None => false,
@@ -25,31 +40,39 @@ impl FileMatcher {
     }
 }
 
-impl From<String> for FileMatcher {
-    fn from(s: String) -> FileMatcher {
+impl TryFrom<String> for FileMatcher {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<FileMatcher, String> {
         if s == "any" {
-            return FileMatcher {
+            return Ok(FileMatcher {
                 any: true,
                 regex: None,
-            };
+                glob: None,
+            });
         }
 
-        let r = match Regex::new(&s) {
-            Ok(r) => r,
-            Err(e) => {
-                println!("Failed to compile file matcher regex: {}", e);
-                process::exit(1);
-            }
-        };
+        if let Some(pattern) = s.strip_prefix("glob:") {
+            let g = Glob::new(pattern.trim())
+                .map_err(|e| format!("Failed to compile file matcher glob: {}", e))?;
+
+            return Ok(FileMatcher {
+                any: false,
+                regex: None,
+                glob: Some(g.compile_matcher()),
+            });
+        }
 
-        FileMatcher {
+        let r =
+            Regex::new(&s).map_err(|e| format!("Failed to compile file matcher regex: {}", e))?;
+        Ok(FileMatcher {
             any: false,
             regex: Some(r),
-        }
+            glob: None,
+        })
     }
 }
 
-#[cfg(feature = "spdx-templates")]
 #[derive(Deserialize)]
 struct SPDXLicenseInfo {
     #[serde(alias = "licenseText")]
@@ -58,103 +81,511 @@ struct SPDXLicenseInfo {
     license_header: Option<String>,
 }
 
+/// The kind of header rendered for a license.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum HeaderType {
+    /// The configured or SPDX provided license template.
+    #[default]
+    Full,
+    /// Only a copyright line and an SPDX-License-Identifier tag.
+    SpdxShort,
+    /// A REUSE header, an SPDX-FileCopyrightText tag with the year and
+    /// authors and an SPDX-License-Identifier tag.
+    Reuse,
+}
+
+/// The options of a license which are rendered into a token, whether
+/// each is set, and the token which the template must then contain.
+type RequiredToken = (&'static str, fn(&Config) -> bool, &'static str);
+
+const REQUIRED_TOKENS: &[RequiredToken] = &[
+    (
+        "year_range",
+        |c| c.includes_year() && c.year_range,
+        "[year]",
+    ),
+    (
+        "start_year",
+        |c| c.includes_year() && c.start_year.is_some(),
+        "[year]",
+    ),
+    (
+        "use_git_year",
+        |c| c.includes_year() && c.use_git_year,
+        "[year]",
+    ),
+    ("year", |c| c.includes_year() && c.year.is_some(), "[year]"),
+    ("authors", |c| !c.authors.is_empty(), "[name of author]"),
+    (
+        "copyrights",
+        |c| !c.copyrights.is_empty(),
+        "[name of author]",
+    ),
+    ("sort_authors", |c| c.sort_authors, "[name of author]"),
+    ("max_authors", |c| c.max_authors > 0, "[name of author]"),
+    ("et_al", |c| c.et_al.is_some(), "[name of author]"),
+    (
+        "authors_prefix",
+        |c| c.authors_prefix.is_some(),
+        "[name of author]",
+    ),
+    (
+        "authors_suffix",
+        |c| c.authors_suffix.is_some(),
+        "[name of author]",
+    ),
+    (
+        "author_fields",
+        |c| c.author_fields.is_some(),
+        "[name of author]",
+    ),
+    (
+        "copyright_symbol",
+        |c| c.copyright_symbol.is_some(),
+        "[copyright_symbol]",
+    ),
+    ("spdx_id", |c| c.spdx_id.is_some(), "[spdx]"),
+];
+
 #[derive(Deserialize)]
 pub struct Config {
     files: FileMatcher,
+    #[serde(default, rename = "type")]
+    header_type: HeaderType,
 
     ident: String,
+    spdx_id: Option<String>,
+    #[serde(default)]
     authors: Authors,
+    /// Copyright lines with authors and a year of their own, rendered
+    /// one below the other in place of the line with authors.
+    #[serde(default)]
+    copyrights: Vec<Copyright>,
+    #[serde(default)]
+    exclude_authors: Vec<String>,
+    #[serde(default)]
+    sort_authors: bool,
+    /// Put before and after the rendered authors, such as "and
+    /// contributors", which stay the same when the authors change.
+    authors_prefix: Option<String>,
+    authors_suffix: Option<String>,
+    #[serde(default)]
+    max_authors: usize,
+    et_al: Option<String>,
+    copyright_symbol: Option<String>,
+    author_fields: Option<Vec<AuthorField>>,
     year: Option<String>,
+    #[serde(default)]
+    year_range: bool,
+    start_year: Option<String>,
+    #[serde(default)]
+    use_git_year: bool,
+    include_year: Option<bool>,
 
     template: Option<String>,
+    /// The copyright line of the header, composed with notice_template
+    /// instead of using template.
+    copyright_template: Option<String>,
+    /// The license notice following the copyright line.
+    notice_template: Option<String>,
     auto_template: Option<bool>,
+    spdx_cache_dir: Option<String>,
+    #[serde(default)]
+    spdx_offline: bool,
+    #[serde(skip)]
+    fetched_template: OnceLock<String>,
 
+    #[serde(default)]
     unwrap_text: bool,
+    #[serde(default)]
+    preserve_line_breaks: bool,
 }
 
 impl Config {
+    fn includes_year(&self) -> bool {
+        self.include_year.unwrap_or(true)
+    }
+
     pub fn file_is_match(&self, s: &str) -> bool {
         self.files.is_match(s)
     }
 
-    #[cfg(feature = "spdx-templates")]
-    async fn fetch_template(&self) -> String {
-        let r = match reqwest::get(&format!("https://spdx.org/licenses/{}.json", &self.ident)).await {
-            Ok(r) => r,
-            Err(e) => {
-                println!("Failed to fetch license template from SPDX: {}", e);
-                process::exit(1);
+    pub fn set_ident(&mut self, ident: &str) {
+        self.ident = ident.to_string();
+    }
+
+    fn spdx_cache_file(&self) -> Option<PathBuf> {
+        let mut path = match &self.spdx_cache_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => {
+                let mut cache = xdg_cache_dir()?;
+                cache.push("licensure");
+                cache.push("spdx");
+                cache
             }
         };
+        path.push(format!("{}.json", self.ident));
+        Some(path)
+    }
 
-        match r.status() {
-            reqwest::StatusCode::NOT_FOUND => {
-                println!(
-                    "{} does not appear to be a valid SPDX identifier, go to https://spdx.org/licenses/ to view a list of valid identifiers",
-                    &self.ident
-                );
-                process::exit(1)
-            }
-            reqwest::StatusCode::OK => (),
-            _ => {
-                println!(
-                    "Failed to fetch license template from SPDX for {}: {:?}",
-                    &self.ident,
-                    r.status()
+    /// Find the SPDX license info JSON for this license, reading it
+    /// from the cache when available and otherwise downloading it and
+    /// storing it in the cache.
+    fn spdx_license_json(&self) -> io::Result<String> {
+        let cache_file = self.spdx_cache_file();
+        if let Some(json) = cache_file
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+        {
+            return Ok(json);
+        }
+
+        if self.spdx_offline {
+            return Err(io::Error::other(format!(
+                "spdx_offline is set but no cached SPDX license info for {} was found at {}, run once without spdx_offline to download it there, or copy it from https://spdx.org/licenses/{}.json",
+                self.ident,
+                self.ident,
+                cache_file
+                    .map_or_else(|| "<no cache dir>".to_string(), |p| p.display().to_string()),
+            )));
+        }
+
+        let json = download_spdx_license_json(&self.ident)?;
+        if let Some(path) = cache_file {
+            let cached = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&path, &json));
+            if let Err(e) = cached {
+                warn!(
+                    "Unable to cache SPDX license info at {}: {}",
+                    path.display(),
+                    e
                 );
-                process::exit(1);
             }
         }
 
-        let json: SPDXLicenseInfo = match r.json().await {
-            Ok(j) => j,
-            Err(e) => {
-                println!("Failed to deserialize SPDX JSON: {}", e);
-                process::exit(1);
-            }
-        };
+        Ok(json)
+    }
 
-        match json.license_header {
+    fn fetch_template(&self) -> io::Result<String> {
+        if let Some(templ) = self.fetched_template.get() {
+            return Ok(templ.clone());
+        }
+
+        let json: SPDXLicenseInfo = serde_json::from_str(&self.spdx_license_json()?)
+            .map_err(|e| io::Error::other(format!("Failed to deserialize SPDX JSON: {}", e)))?;
+        let templ = match json.license_header {
             Some(header) => header,
             None => json.license_text,
+        };
+
+        // Another thread may have fetched it meanwhile, either copy is
+        // the same.
+        Ok(self.fetched_template.get_or_init(|| templ).clone())
+    }
+
+    /// The authors of file, sorted when sort_authors is set.
+    fn get_authors(&self, authors: &Authors, file: &str) -> Authors {
+        let mut authors = if authors.is_from_git() {
+            Authors::from_git(file, &self.exclude_authors)
+        } else {
+            authors.clone()
+        };
+        if self.sort_authors {
+            authors.sort();
+        }
+
+        authors
+    }
+
+    /// Determine the year and start year for file, taking the year
+    /// the file was added to git when use_git_year is set.
+    fn get_years(&self, file: &str) -> (Option<String>, Option<String>) {
+        let git_year = if self.use_git_year {
+            git::creation_year(file)
+        } else {
+            None
+        };
+
+        if self.year_range {
+            (
+                self.year.clone(),
+                git_year.or_else(|| self.start_year.clone()),
+            )
+        } else {
+            (git_year.or_else(|| self.year.clone()), None)
+        }
+    }
+
+    /// Check that the template has the tokens which the options set
+    /// for this license render into, as the options do nothing without
+    /// them. Templates fetched from SPDX are not known until they are
+    /// used and are not checked.
+    pub fn check_tokens(&self) -> Result<(), String> {
+        let composed;
+        let template = match &self.template {
+            _ if self.is_composed() => {
+                composed = self.composed_template();
+                &composed
+            }
+            Some(template) => template,
+            None => return Ok(()),
+        };
+
+        for (option, is_set, token) in REQUIRED_TOKENS {
+            if is_set(self) && !template.contains(token) {
+                return Err(format!(
+                    "the template of the license for {} does not contain the {} token, which {} is rendered into",
+                    self.ident, token, option
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn composed_template(&self) -> String {
+        let notice = self
+            .notice_template
+            .as_deref()
+            .unwrap_or(DEFAULT_NOTICE_TEMPLATE);
+        if self.header_type == HeaderType::Reuse {
+            // The tags are separated by a blank line, as reuse annotate
+            // writes them.
+            return compose_template(
+                self.copyright_template
+                    .as_deref()
+                    .unwrap_or(DEFAULT_REUSE_COPYRIGHT_TEMPLATE),
+                &format!("\n{}", notice),
+            );
         }
+
+        compose_template(
+            self.copyright_template
+                .as_deref()
+                .unwrap_or(DEFAULT_COPYRIGHT_TEMPLATE),
+            notice,
+        )
     }
 
-    #[cfg(not(feature = "spdx-templates"))]
-    async fn fetch_template(&self) -> String {
-        eprintln!("Licensure is not compiled with 'spdx-templates' feature, so it cannot fetch SPDX license templates");
-        process::exit(1);
+    /// Whether the header is composed of a copyright line and a notice,
+    /// as spdx-short and REUSE headers are, rather than a single
+    /// template.
+    fn is_composed(&self) -> bool {
+        self.header_type != HeaderType::Full
+            || self.copyright_template.is_some()
+            || self.notice_template.is_some()
     }
 
-    pub async fn get_template(&self) -> Template {
+    pub async fn get_template(&self, file: &str) -> io::Result<Template> {
+        if self.template.is_some()
+            && (self.copyright_template.is_some() || self.notice_template.is_some())
+        {
+            return Err(io::Error::other(format!("The license definition for {} sets template along with copyright_template or notice_template, please use either template or the other two", self.ident)));
+        }
+
         let auto_templ;
+        let composed;
         let t = match &self.template {
+            _ if self.is_composed() => {
+                composed = self.composed_template();
+                &composed
+            }
             Some(ref t) => t,
             None => {
                 if self.auto_template.unwrap_or(false) {
-                    auto_templ = self.fetch_template().await;
+                    auto_templ = self.fetch_template()?;
                     &auto_templ
                 } else {
-                    println!("auto_template not enabled and no template provided, please add a template option to the license definition for {}. Exitting", self.ident);
-                    process::exit(1);
+                    return Err(io::Error::other(format!("auto_template not enabled and no template provided, please add a template option to the license definition for {}", self.ident)));
                 }
             }
         };
 
+        let (year, start_year) = self.get_years(file);
         let t = Template::new(
             t,
             Context {
                 ident: self.ident.clone(),
-                year: self.year.clone(),
-                authors: self.authors.clone(),
-                unwrap_text: self.unwrap_text,
+                spdx_id: self.spdx_id.clone(),
+                extension: Path::new(file)
+                    .file_name()
+                    .and_then(|name| name.to_str()?.split_once('.'))
+                    .map(|(_, extension)| extension.to_string()),
+                year,
+                start_year,
+                year_range: self.year_range,
+                omit_year: !self.includes_year(),
+                authors: self.get_authors(&self.authors, file),
+                copyrights: self
+                    .copyrights
+                    .iter()
+                    .map(|copyright| Copyright {
+                        authors: self.get_authors(&copyright.authors, file),
+                        year: copyright.year.clone(),
+                    })
+                    .collect(),
+                authors_prefix: self.authors_prefix.clone(),
+                authors_suffix: self.authors_suffix.clone(),
+                max_authors: self.max_authors,
+                et_al: self.et_al.clone(),
+                copyright_symbol: self.copyright_symbol.clone(),
+                author_fields: self.author_fields.clone(),
+                unwrap_text: self.unwrap_text && !self.is_composed(),
+                preserve_line_breaks: self.preserve_line_breaks,
+                project: None,
+                vcs_url: None,
+                license_url: None,
+                tokens: Default::default(),
             },
         );
 
-        if self.auto_template.unwrap_or(false) {
-            return t.set_spdx_template(true);
+        if self.auto_template.unwrap_or(false) && !self.is_composed() {
+            return Ok(t.set_spdx_template(true));
         }
 
-        t
+        Ok(t.set_reuse(self.header_type == HeaderType::Reuse))
+    }
+}
+
+#[cfg(feature = "spdx-templates")]
+fn download_spdx_license_json(ident: &str) -> io::Result<String> {
+    let r = reqwest::blocking::get(format!("https://spdx.org/licenses/{}.json", ident)).map_err(
+        |e| io::Error::other(format!("Failed to fetch license template from SPDX: {}", e)),
+    )?;
+
+    match r.status() {
+        reqwest::StatusCode::NOT_FOUND => {
+            return Err(io::Error::other(format!(
+                "{} does not appear to be a valid SPDX identifier, go to https://spdx.org/licenses/ to view a list of valid identifiers",
+                ident
+            )));
+        }
+        reqwest::StatusCode::OK => (),
+        status => {
+            return Err(io::Error::other(format!(
+                "Failed to fetch license template from SPDX for {}: {:?}",
+                ident, status
+            )));
+        }
+    }
+
+    r.text().map_err(|e| {
+        io::Error::other(format!(
+            "Failed to read SPDX license info for {}: {}",
+            ident, e
+        ))
+    })
+}
+
+#[cfg(not(feature = "spdx-templates"))]
+fn download_spdx_license_json(_ident: &str) -> io::Result<String> {
+    Err(io::Error::other("Licensure is not compiled with 'spdx-templates' feature, so it cannot fetch SPDX license templates"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_matcher() {
+        let any = FileMatcher::try_from("any".to_string()).unwrap();
+        assert!(any.is_match("src/main.rs"));
+
+        let regex = FileMatcher::try_from(r"src/.*\.rs".to_string()).unwrap();
+        assert!(regex.is_match("src/main.rs"));
+        assert!(!regex.is_match("vendor/lib.c"));
+
+        let glob = FileMatcher::try_from("glob: vendor/**".to_string()).unwrap();
+        assert!(glob.is_match("vendor/zlib/inflate.c"));
+        assert!(!glob.is_match("src/vendor.rs"));
+    }
+
+    #[test]
+    fn test_fetch_template_from_cache() {
+        let dir = crate::testing::TempDir::new("spdx-cache");
+        dir.write(
+            "Test-1.0.json",
+            r#"{"licenseText": "full text", "standardLicenseHeader": "header text"}"#,
+        );
+
+        let cfg: Config = serde_yaml::from_str(&format!(
+            "{{files: any, ident: Test-1.0, authors: [], auto_template: true, spdx_offline: true, spdx_cache_dir: '{}'}}",
+            dir.path().display()
+        ))
+        .unwrap();
+        assert_eq!("header text", cfg.fetch_template().unwrap());
+
+        let cfg: Config = serde_yaml::from_str(&format!(
+            "{{files: any, ident: Missing-1.0, authors: [], auto_template: true, spdx_offline: true, spdx_cache_dir: '{}'}}",
+            dir.path().display()
+        ))
+        .unwrap();
+        let err = cfg.fetch_template().unwrap_err().to_string();
+        assert!(err.contains(&dir.join("Missing-1.0.json").display().to_string()));
+    }
+
+    #[test]
+    fn test_copyright_and_notice_templates() {
+        let render = |cfg: &str| {
+            let cfg: Config = serde_yaml::from_str(cfg).unwrap();
+            futures::executor::block_on(cfg.get_template("main.rs"))?.render()
+        };
+        assert_eq!(
+            "Copyright (C) 2020 Me\nSPDX-License-Identifier: MIT\n",
+            render("{files: any, ident: MIT, year: '2020', authors: [{name: Me}], copyright_template: 'Copyright [copyright_symbol] [year] [name of author]'}").unwrap()
+        );
+        assert_eq!(
+            "Copyright 2020 Me\nLicensed under the MIT License (SPDX: MIT)\n",
+            render("{files: any, ident: MIT License, spdx_id: MIT, year: '2020', authors: [{name: Me}], notice_template: 'Licensed under the [ident] (SPDX: [spdx])'}").unwrap()
+        );
+        assert_eq!(
+            "Copyright 2020 Acme Inc.\nCopyright 2024 Me\nSPDX-License-Identifier: MIT\n",
+            render("{files: any, ident: MIT, type: spdx-short, year: '2024', copyrights: [{authors: [{name: Acme Inc.}], year: '2020'}, {authors: [{name: Me}]}]}").unwrap()
+        );
+        assert!(
+            render("{files: any, ident: MIT, authors: [], template: x, notice_template: y}")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_check_tokens() {
+        let check = |cfg: &str| serde_yaml::from_str::<Config>(cfg).unwrap().check_tokens();
+        assert!(check("{files: any, ident: MIT, authors: [{name: Me}], year_range: true, template: 'Copyright [year] [name of author]'}").is_ok());
+        assert!(check("{files: any, ident: MIT, authors: [], template: 'MIT licensed'}").is_ok());
+        assert!(check("{files: any, ident: MIT, authors: [], year_range: true, include_year: false, template: 'MIT licensed'}").is_ok());
+        assert!(check("{files: any, ident: MIT, authors: [], auto_template: true}").is_ok());
+
+        let e = check(
+            "{files: any, ident: MIT, authors: [], year_range: true, template: 'Copyright Me'}",
+        )
+        .unwrap_err();
+        assert!(e.contains("[year]") && e.contains("year_range"), "{}", e);
+        let e =
+            check("{files: any, ident: MIT, authors: [{name: Me}], template: 'Copyright [year]'}")
+                .unwrap_err();
+        assert!(
+            e.contains("[name of author]") && e.contains("authors"),
+            "{}",
+            e
+        );
+        let e = check(
+            "{files: any, ident: MIT, authors: [], spdx_id: MIT, notice_template: 'MIT licensed'}",
+        )
+        .unwrap_err();
+        assert!(e.contains("[spdx]"), "{}", e);
+        let e = check(
+            "{files: any, ident: MIT, authors: [], authors_suffix: and contributors, template: 'MIT licensed'}",
+        )
+        .unwrap_err();
+        assert!(e.contains("authors_suffix"), "{}", e);
+
+        assert!(serde_yaml::from_str::<crate::config::LicenseConfigList>(
+            "[{files: any, ident: MIT, authors: from-git, template: 'Copyright [year]'}]"
+        )
+        .is_err());
     }
 }
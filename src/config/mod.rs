@@ -1,83 +1,414 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::env;
-use std::fs::File;
+use std::fs;
 use std::io;
-use std::path::PathBuf;
-use std::process;
+use std::path::{Path, PathBuf};
 
-use regex::RegexSet;
+use regex::{Regex, RegexSet};
 use serde::Deserialize;
 
 mod comment;
 mod default;
 mod license;
 
-pub use default::DEFAULT_CONFIG;
+pub use default::{DEFAULT_CONFIG, DEFAULT_CONFIG_TOML};
 
+use crate::cache;
 use crate::comments::Comment;
-use crate::config::comment::get_filetype;
-use crate::config::comment::Config as CommentConfig;
+use crate::config::comment::{shebang_extension, Config as CommentConfig};
 use crate::config::license::Config as LicenseConfig;
-use crate::template::Template;
+use crate::error::LicensureError;
+use crate::git;
+use crate::template::{Template, LICENSE_URL_TOKEN, VCS_URL_TOKEN};
 
 fn def_change_in_place() -> bool {
     false
 }
 
+fn def_copyright_search_lines() -> usize {
+    10
+}
+
+fn def_opt_out_marker() -> String {
+    "licensure:disable".to_string()
+}
+
+/// Emacs -*- mode: python -*- lines and Vim modelines such as
+/// "vim: set ft=python:".
+fn def_modeline_patterns() -> RegexList {
+    RegexList::try_from(vec![
+        r"-\*-.*-\*-".to_string(),
+        r"\s(?:vi|vim|ex):".to_string(),
+    ])
+    .expect("default modeline patterns should be valid")
+}
+
+/// The line ending used for inserted headers.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    /// Use the line ending most common in each file.
+    #[default]
+    Auto,
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    /// Return the line ending to use for content.
+    pub fn for_content(self, content: &str) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Auto => {
+                let crlf = content.matches("\r\n").count();
+                let lf = content.matches('\n').count() - crlf;
+                if crlf > lf {
+                    "\r\n"
+                } else {
+                    "\n"
+                }
+            }
+        }
+    }
+}
+
+/// Tokens which are substituted by Licensure itself and so cannot be
+/// defined in the config.
+const BUILTIN_TOKENS: &[&str] = &[
+    "year",
+    "name of author",
+    "ident",
+    "spdx",
+    "project",
+    "vcs_url",
+    "license_url",
+    "copyright_symbol",
+    "yyyy",
+    "name of copyright owner",
+];
+
+/// The URL of the full text of common licenses by SPDX identifier, used
+/// for [license_url] when license_urls has none for the identifier.
+const DEFAULT_LICENSE_URLS: &[(&str, &str)] = &[
+    ("AGPL-3.0", "https://www.gnu.org/licenses/agpl-3.0.html"),
+    ("Apache-2.0", "https://www.apache.org/licenses/LICENSE-2.0"),
+    (
+        "BSD-2-Clause",
+        "https://opensource.org/licenses/BSD-2-Clause",
+    ),
+    (
+        "BSD-3-Clause",
+        "https://opensource.org/licenses/BSD-3-Clause",
+    ),
+    (
+        "GPL-2.0",
+        "https://www.gnu.org/licenses/old-licenses/gpl-2.0.html",
+    ),
+    ("GPL-3.0", "https://www.gnu.org/licenses/gpl-3.0.html"),
+    ("ISC", "https://opensource.org/licenses/ISC"),
+    (
+        "LGPL-2.1",
+        "https://www.gnu.org/licenses/old-licenses/lgpl-2.1.html",
+    ),
+    ("LGPL-3.0", "https://www.gnu.org/licenses/lgpl-3.0.html"),
+    ("MIT", "https://opensource.org/licenses/MIT"),
+    ("MPL-2.0", "https://mozilla.org/MPL/2.0/"),
+    ("Unlicense", "https://unlicense.org"),
+];
+
+/// User defined template tokens, each name is substituted for [name].
+#[derive(Clone, Default, Deserialize)]
+#[serde(try_from = "BTreeMap<String, String>")]
+pub struct TokenMap {
+    tokens: BTreeMap<String, String>,
+}
+
+impl TryFrom<BTreeMap<String, String>> for TokenMap {
+    type Error = String;
+
+    fn try_from(tokens: BTreeMap<String, String>) -> Result<TokenMap, String> {
+        for name in tokens.keys() {
+            if BUILTIN_TOKENS.contains(&name.as_str()) || name.starts_with("env:") {
+                return Err(format!(
+                    "token [{}] is built in to licensure and cannot be redefined",
+                    name
+                ));
+            }
+
+            if name.is_empty() || name.contains(['[', ']']) {
+                return Err(format!("invalid token name \"{}\"", name));
+            }
+        }
+
+        Ok(TokenMap { tokens })
+    }
+}
+
+/// Information about the project used in templates.
+#[derive(Deserialize)]
+pub struct ProjectConfig {
+    pub name: Option<String>,
+    /// Leave [vcs_url] blank when there is no origin remote rather
+    /// than failing.
+    #[serde(default)]
+    pub vcs_url_optional: bool,
+}
+
 #[derive(Deserialize)]
 pub struct Config {
     #[serde(default = "def_change_in_place")]
     pub change_in_place: bool,
+    #[serde(default)]
+    pub line_ending: LineEnding,
+    #[serde(default)]
+    pub skip_if_any_copyright: bool,
+    #[serde(default = "def_copyright_search_lines")]
+    pub copyright_search_lines: usize,
+    /// Files with this text in their first copyright_search_lines lines
+    /// are never licensed.
+    #[serde(default = "def_opt_out_marker")]
+    pub opt_out_marker: String,
+    /// Lines at the top of a file matching one of these regexes, such
+    /// as editor modelines, are kept above the header like a shebang.
+    #[serde(default = "def_modeline_patterns")]
+    pub modeline_patterns: RegexList,
+    pub project: Option<ProjectConfig>,
+    #[serde(default)]
+    pub tokens: TokenMap,
+    /// The URL substituted for [license_url] by license identifier,
+    /// taking precedence over the built-in ones.
+    #[serde(default)]
+    pub license_urls: BTreeMap<String, String>,
+    #[serde(default)]
     pub excludes: RegexList,
+    /// When not empty only files matching one of these regexes are
+    /// licensed, excludes still apply to them.
+    #[serde(default)]
+    pub includes: RegexList,
     pub licenses: LicenseConfigList,
+    #[serde(default)]
     pub comments: CommentConfigList,
+    /// A hash of the effective config, which changes whenever the
+    /// config does.
+    #[serde(skip)]
+    fingerprint: u64,
+    /// Warnings about how the config files were found, for the caller
+    /// to show.
+    #[serde(skip)]
+    warnings: Vec<String>,
+}
+
+/// The settings used to license files without a config file.
+pub struct InlineConfig {
+    pub template: String,
+    pub ident: String,
+    /// Authors formatted as "Name" or "Name <email>".
+    pub authors: Vec<String>,
+    /// When set used as the line comment prefix for every file instead
+    /// of the built-in comment styles.
+    pub comment_prefix: Option<String>,
+    pub columns: Option<usize>,
 }
 
 impl Config {
-    pub fn add_exclude(&mut self, pat: &str) {
-        self.excludes.add_exclude(pat);
+    /// Build a config which licenses every file with the given inline
+    /// settings.
+    pub fn inline(inline: InlineConfig) -> Result<Config, LicensureError> {
+        let authors: Vec<serde_yaml::Value> = inline
+            .authors
+            .iter()
+            .map(|author| author_value(author))
+            .collect();
+
+        let mut license = serde_yaml::Mapping::new();
+        license.insert("files".into(), "any".into());
+        license.insert("ident".into(), inline.ident.into());
+        license.insert("authors".into(), authors.into());
+        license.insert("template".into(), inline.template.into());
+
+        let mut config = serde_yaml::Mapping::new();
+        config.insert("licenses".into(), vec![license].into());
+        if let Some(prefix) = inline.comment_prefix {
+            let mut commenter = serde_yaml::Mapping::new();
+            commenter.insert("type".into(), "line".into());
+            commenter.insert("comment_char".into(), prefix.into());
+
+            // A glob matching every file, so that the prefix is used
+            // over the built-in comment styles for all of them.
+            let mut comment = serde_yaml::Mapping::new();
+            comment.insert("extensions".into(), vec!["*"].into());
+            comment.insert("commenter".into(), commenter.into());
+            if let Some(columns) = inline.columns {
+                comment.insert("columns".into(), (columns as u64).into());
+            }
+            config.insert("comments".into(), vec![comment].into());
+        }
+
+        Config::from_value(serde_yaml::Value::Mapping(config)).map_err(|e| LicensureError::Config {
+            paths: Vec::new(),
+            message: e.to_string(),
+        })
+    }
+
+    fn from_value(value: serde_yaml::Value) -> Result<Config, serde_yaml::Error> {
+        let mut config: Config = serde_yaml::from_value(value.clone())?;
+        config.fingerprint = cache::hash(&serde_yaml::to_string(&value)?);
+        Ok(config)
+    }
+
+    /// A hash of the config, including changes made after loading it.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Warnings found while looking for the config files, such as both
+    /// a TOML and a YAML config file being in the same directory.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn add_exclude(&mut self, pat: &str) -> Result<(), LicensureError> {
+        self.fingerprint = cache::hash(&(self.fingerprint, "exclude", pat));
+        self.excludes.add_exclude(pat)
+    }
+
+    /// Exclude the files whose name ends with the extension ext, in any
+    /// case. ext may be a compound one such as min.js.
+    pub fn add_exclude_ext(&mut self, ext: &str) -> Result<(), LicensureError> {
+        let ext = ext.trim().trim_start_matches('.');
+        if ext.is_empty() {
+            return Err(LicensureError::Config {
+                paths: Vec::new(),
+                message: "excluded extensions must not be empty".to_string(),
+            });
+        }
+
+        self.add_exclude(&format!(r"(?i)\.{}$", regex::escape(ext)))
+    }
+
+    /// Whether file should be licensed at all, it must match an include
+    /// when there are any and must not match an exclude.
+    pub fn is_selected(&self, file: &str) -> bool {
+        (self.includes.is_empty() || self.includes.is_match(file)) && !self.excludes.is_match(file)
+    }
+
+    /// Use ident as the license identifier of every license.
+    pub fn set_ident(&mut self, ident: &str) {
+        self.fingerprint = cache::hash(&(self.fingerprint, "ident", ident));
+        self.licenses.set_ident(ident);
+    }
+
+    /// The URL of the full text of the license ident, from license_urls
+    /// or else the built-in URLs, which also apply to the -only and
+    /// -or-later variants of the GNU licenses.
+    fn license_url(&self, ident: &str) -> Option<String> {
+        if let Some(url) = self.license_urls.get(ident) {
+            return Some(url.clone());
+        }
+
+        let base = ident
+            .strip_suffix("-only")
+            .or_else(|| ident.strip_suffix("-or-later"))
+            .unwrap_or(ident);
+        DEFAULT_LICENSE_URLS
+            .iter()
+            .find(|(id, _)| *id == base)
+            .map(|(_, url)| url.to_string())
+    }
+
+    /// Find the template of the license which applies to filename,
+    /// with the project wide settings applied. None when no license
+    /// applies to filename.
+    pub async fn get_template(&self, filename: &str) -> io::Result<Option<Template>> {
+        let templ = match self.licenses.get_template(filename).await? {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+        let project = self.project.as_ref().and_then(|p| p.name.clone());
+        // Finding the remote runs git, which is only worth it for the
+        // templates using it.
+        let vcs_url = if templ.uses_token(VCS_URL_TOKEN) {
+            let optional = self.project.as_ref().is_some_and(|p| p.vcs_url_optional);
+            git::remote_url().or_else(|| optional.then(String::new))
+        } else {
+            None
+        };
+        let license_url = if templ.uses_token(LICENSE_URL_TOKEN) {
+            self.license_url(templ.ident())
+        } else {
+            None
+        };
+        Ok(Some(
+            templ
+                .set_project(project)
+                .set_vcs_url(vcs_url)
+                .set_license_url(license_url)
+                .set_tokens(self.tokens.tokens.clone()),
+        ))
     }
 }
 
 #[derive(Deserialize)]
-#[serde(from = "Vec<String>")]
+#[serde(try_from = "Vec<String>")]
 pub struct RegexList {
     regex: RegexSet,
 }
 
+impl Default for RegexList {
+    fn default() -> RegexList {
+        RegexList {
+            regex: RegexSet::empty(),
+        }
+    }
+}
+
 impl RegexList {
     pub fn is_match(&self, s: &str) -> bool {
         self.regex.is_match(s)
     }
 
-    pub fn add_exclude(&mut self, pat: &str) {
-        let mut old_pats = Vec::from(self.regex.patterns());
-        let mut new_pats = vec![pat.to_string()];
-        new_pats.append(&mut old_pats);
-        self.regex = match RegexSet::new(&new_pats) {
-            Ok(r) => r,
-            Err(e) => {
-                println!("Failed to compile exclude pattern: {}", e);
-                process::exit(1);
-            }
-        };
+    pub fn is_empty(&self) -> bool {
+        self.regex.is_empty()
+    }
+
+    /// Exclude files matching pat as well as those already excluded.
+    pub fn add_exclude(&mut self, pat: &str) -> Result<(), LicensureError> {
+        let mut pats = Vec::from(self.regex.patterns());
+        pats.push(pat.to_string());
+        *self = RegexList::try_from(pats)?;
+        Ok(())
     }
 }
 
-impl From<Vec<String>> for RegexList {
-    fn from(rgxs: Vec<String>) -> RegexList {
-        RegexList {
-            regex: match RegexSet::new(&rgxs) {
-                Ok(r) => r,
-                Err(e) => {
-                    println!("Failed to compile exclude pattern: {}", e);
-                    process::exit(1);
-                }
-            },
+impl TryFrom<Vec<String>> for RegexList {
+    type Error = LicensureError;
+
+    /// Compile rgxs once, naming the first pattern which is not a valid
+    /// regex. When they only fail together, such as by being too big,
+    /// the pattern named is all of them.
+    fn try_from(rgxs: Vec<String>) -> Result<RegexList, LicensureError> {
+        if let Some((pat, error)) = rgxs
+            .iter()
+            .find_map(|pat| Regex::new(pat).err().map(|e| (pat, e)))
+        {
+            return Err(LicensureError::Regex {
+                pattern: pat.clone(),
+                error,
+            });
         }
+
+        RegexSet::new(&rgxs)
+            .map(|regex| RegexList { regex })
+            .map_err(|error| LicensureError::Regex {
+                pattern: rgxs.join("|"),
+                error,
+            })
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Default, Deserialize)]
 #[serde(from = "Vec<CommentConfig>")]
 pub struct CommentConfigList {
     cfgs: Vec<CommentConfig>,
@@ -90,42 +421,93 @@ impl From<Vec<CommentConfig>> for CommentConfigList {
 }
 
 impl CommentConfigList {
+    /// Find the comment configuration for filename. Configurations
+    /// from the config file are checked in order, "any" included, and
+    /// only when none matches are the built-in defaults used. Block
+    /// commenters from the config file follow the .editorconfig
+    /// indentation of filename.
     pub fn get_commenter(&self, filename: &str) -> (CommentConfig, Box<dyn Comment>) {
-        let file_type = get_filetype(filename);
+        let cfg = self.find(filename).unwrap_or_else(CommentConfig::default);
+        let commenter = cfg.commenter();
+        (cfg, commenter)
+    }
 
-        for c in &self.cfgs {
-            if c.matches(file_type) {
-                let cfg = c.clone();
-                return (cfg, c.commenter());
-            }
+    /// Whether a comment configuration from the config file, or a
+    /// built-in one, applies to filename rather than the default.
+    pub fn has_commenter(&self, filename: &str) -> bool {
+        self.find(filename).is_some()
+    }
+
+    /// The name to choose the comment style of filename by. It is
+    /// filename itself unless nothing but "any" or the default applies
+    /// to it and its first line is the shebang of a known interpreter,
+    /// then it is commented like a file of that interpreter's language.
+    /// The first line is only read when needed.
+    pub fn commented_as<F>(&self, filename: &str, first_line: F) -> String
+    where
+        F: FnOnce() -> Option<String>,
+    {
+        if self.is_known_by_name(filename) {
+            return filename.to_string();
         }
 
-        let def = CommentConfig::default();
-        (def, CommentConfig::default().commenter())
+        match first_line().as_deref().and_then(shebang_extension) {
+            Some(extension) => format!("{}.{}", filename, extension),
+            None => filename.to_string(),
+        }
+    }
+
+    fn find(&self, filename: &str) -> Option<CommentConfig> {
+        self.cfgs
+            .iter()
+            .find(|c| c.matches(filename))
+            .cloned()
+            .map(|c| c.with_editorconfig(filename))
+            .or_else(|| CommentConfig::builtin(filename))
+    }
+
+    /// Whether the name of filename tells its comment style, through a
+    /// configuration from the config file other than "any" or a
+    /// built-in one.
+    fn is_known_by_name(&self, filename: &str) -> bool {
+        self.cfgs.iter().any(|c| !c.is_any() && c.matches(filename))
+            || CommentConfig::builtin(filename).is_some()
     }
 }
 
 #[derive(Deserialize)]
-#[serde(from = "Vec<LicenseConfig>")]
+#[serde(try_from = "Vec<LicenseConfig>")]
 pub struct LicenseConfigList {
     cfgs: Vec<LicenseConfig>,
 }
 
 impl LicenseConfigList {
-    pub async fn get_template(&self, filename: &str) -> Option<Template> {
+    pub fn set_ident(&mut self, ident: &str) {
+        for cfg in &mut self.cfgs {
+            cfg.set_ident(ident);
+        }
+    }
+
+    pub async fn get_template(&self, filename: &str) -> io::Result<Option<Template>> {
         for cfg in &self.cfgs {
             if cfg.file_is_match(filename) {
-                return Some(cfg.get_template().await);
+                return cfg.get_template(filename).await.map(Some);
             }
         }
 
-        None
+        Ok(None)
     }
 }
 
-impl From<Vec<LicenseConfig>> for LicenseConfigList {
-    fn from(cfgs: Vec<LicenseConfig>) -> LicenseConfigList {
-        LicenseConfigList { cfgs }
+impl TryFrom<Vec<LicenseConfig>> for LicenseConfigList {
+    type Error = String;
+
+    fn try_from(cfgs: Vec<LicenseConfig>) -> Result<LicenseConfigList, String> {
+        for cfg in &cfgs {
+            cfg.check_tokens()?;
+        }
+
+        Ok(LicenseConfigList { cfgs })
     }
 }
 
@@ -143,53 +525,583 @@ pub fn xdg_config_dir() -> Option<PathBuf> {
     }
 }
 
-/// Walk up from the current working directory searching for
-/// the first .licensure.yml config file available else find the
-/// global config file.
-fn find_config_file() -> Option<PathBuf> {
-    if let Ok(mut cwd) = env::current_dir() {
-        loop {
-            cwd.push(".licensure.yml");
-            if cwd.exists() {
-                return Some(cwd);
+pub fn xdg_cache_dir() -> Option<PathBuf> {
+    match env::var("XDG_CACHE_HOME") {
+        Ok(d) => Some(PathBuf::from(d)),
+        Err(_) => match env::var("HOME") {
+            Ok(home) => {
+                let mut home_dir = PathBuf::from(home);
+                home_dir.push(".cache");
+                Some(home_dir)
             }
+            Err(_) => None,
+        },
+    }
+}
 
-            // Pop the .licensure.yml file we added
-            cwd.pop();
+/// Find a config file named name with either a .toml or .yml
+/// extension in dir, preferring TOML when both exist, which is added
+/// to warnings.
+fn find_in_dir(dir: &Path, name: &str, warnings: &mut Vec<String>) -> Option<PathBuf> {
+    let toml = dir.join(format!("{}.toml", name));
+    let yaml = dir.join(format!("{}.yml", name));
+    match (toml.exists(), yaml.exists()) {
+        (true, true) => {
+            warnings.push(format!(
+                "found both {} and {}, using {}",
+                toml.display(),
+                yaml.display(),
+                toml.display()
+            ));
+            Some(toml)
+        }
+        (true, false) => Some(toml),
+        (false, true) => Some(yaml),
+        (false, false) => None,
+    }
+}
 
-            // Move up a directory checking if we have hit root yet
-            if !cwd.pop() {
-                break;
-            }
+/// Walk up from the current working directory searching for
+/// the first .licensure.toml or .licensure.yml config file available.
+/// The search stops at the root of the git repository, like git does
+/// for its own config, or at the filesystem root outside of one.
+fn find_local_config_file(warnings: &mut Vec<String>) -> Option<PathBuf> {
+    find_config_file_from(env::current_dir().ok()?, warnings)
+}
+
+fn find_config_file_from(mut cwd: PathBuf, warnings: &mut Vec<String>) -> Option<PathBuf> {
+    loop {
+        if let Some(path) = find_in_dir(&cwd, ".licensure", warnings) {
+            return Some(path);
+        }
+
+        if cwd.join(".git").exists() {
+            return None;
+        }
+
+        // Move up a directory checking if we have hit root yet
+        if !cwd.pop() {
+            return None;
+        }
+    }
+}
+
+/// Find the global config file in $XDG_CONFIG_HOME/licensure, or the
+/// older $XDG_CONFIG_HOME/.licensure location.
+fn find_global_config_file(warnings: &mut Vec<String>) -> Option<PathBuf> {
+    let config_dir = xdg_config_dir()?;
+    ["licensure", ".licensure"]
+        .iter()
+        .find_map(|dir| find_in_dir(&config_dir.join(dir), "config", warnings))
+}
+
+/// Read a TOML or YAML config file into a generic value so that it can
+/// be merged with other config files before being deserialized.
+fn read_config_value(path: &Path) -> Result<serde_yaml::Value, LicensureError> {
+    let content = fs::read_to_string(path)?;
+    let value = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str::<toml::Value>(&content)
+            .map_err(|e| e.to_string())
+            .and_then(|v| serde_yaml::to_value(v).map_err(|e| e.to_string()))
+            .map_err(|e| LicensureError::config(path, format!("invalid TOML: {}", e)))?
+    } else {
+        serde_yaml::from_str(&content)
+            .map_err(|e| LicensureError::config(path, format!("invalid YAML: {}", e)))?
+    };
+
+    read_license_files(value, path)
+}
+
+/// Replace the template_file and authors_file of each license in the
+/// config value read from path with the content of those files as its
+/// template and authors. Relative files are found from the directory
+/// of the config file, so that the global and local configs can each
+/// have their own.
+fn read_license_files(
+    mut value: serde_yaml::Value,
+    path: &Path,
+) -> Result<serde_yaml::Value, LicensureError> {
+    let licenses = match value
+        .get_mut("licenses")
+        .and_then(serde_yaml::Value::as_sequence_mut)
+    {
+        Some(licenses) => licenses,
+        None => return Ok(value),
+    };
+
+    for license in licenses
+        .iter_mut()
+        .filter_map(serde_yaml::Value::as_mapping_mut)
+    {
+        if let Some(template) = read_license_file(license, "template_file", "template", path)? {
+            license.insert("template".into(), template.into());
+        }
+
+        if let Some(authors) = read_license_file(license, "authors_file", "authors", path)? {
+            let authors: Vec<serde_yaml::Value> = authors
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(author_value)
+                .collect();
+            license.insert("authors".into(), authors.into());
         }
     }
 
-    if let Some(mut global) = xdg_config_dir() {
-        global.push(".licensure");
-        global.push("config.yml");
-        if global.exists() {
-            return Some(global);
+    Ok(value)
+}
+
+/// Remove the key option, a path, from license and read the file it
+/// names from the directory of the config file at path. Setting the
+/// option along with replaces, the option it stands for, is an error.
+fn read_license_file(
+    license: &mut serde_yaml::Mapping,
+    key: &str,
+    replaces: &str,
+    path: &Path,
+) -> Result<Option<String>, LicensureError> {
+    let file = match license.remove(&serde_yaml::Value::from(key)) {
+        Some(serde_yaml::Value::String(file)) => file,
+        Some(_) => {
+            return Err(LicensureError::config(
+                path,
+                format!("{} must be a path", key),
+            ))
         }
+        None => return Ok(None),
+    };
+
+    let file_path = path.parent().unwrap_or(Path::new("")).join(&file);
+    if license.contains_key(&serde_yaml::Value::from(replaces)) {
+        return Err(LicensureError::config(
+            path,
+            format!(
+                "a license sets both {} and {} {}, please use one of them",
+                replaces, key, file
+            ),
+        ));
     }
 
-    None
+    let bytes = fs::read(&file_path).map_err(|e| {
+        LicensureError::config(
+            path,
+            format!("unable to read the {} {}: {}", key, file_path.display(), e),
+        )
+    })?;
+    let content = String::from_utf8(bytes).map_err(|_| {
+        LicensureError::config(
+            path,
+            format!("the {} {} is not valid UTF-8", key, file_path.display()),
+        )
+    })?;
+    Ok(Some(content))
+}
+
+/// The config value of an author written as Name or Name <email>.
+fn author_value(author: &str) -> serde_yaml::Value {
+    let mut holder = serde_yaml::Mapping::new();
+    let (name, email) = match author.split_once('<') {
+        Some((name, email)) => (name.trim(), Some(email.trim_end_matches('>').trim())),
+        None => (author.trim(), None),
+    };
+    holder.insert("name".into(), name.into());
+    if let Some(email) = email {
+        holder.insert("email".into(), email.into());
+    }
+    serde_yaml::Value::Mapping(holder)
 }
 
-pub fn load_config() -> Result<Config, io::Error> {
-    match find_config_file() {
-        Some(path) => {
-            let f = File::open(path.clone())?;
-            match serde_yaml::from_reader(f) {
-                Ok(c) => Ok(c),
-                Err(e) => Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Invalid YAML in {}: {}", path.display(), e),
-                )),
+/// Merge local over global. Mappings are merged key by key with the
+/// local value winning, any other local value replaces the global one
+/// wholesale.
+fn merge_config_values(global: serde_yaml::Value, local: serde_yaml::Value) -> serde_yaml::Value {
+    match (global, local) {
+        (serde_yaml::Value::Mapping(mut global), serde_yaml::Value::Mapping(local)) => {
+            for (key, value) in local {
+                let merged = match global.remove(&key) {
+                    Some(existing) => merge_config_values(existing, value),
+                    None => value,
+                };
+                global.insert(key, merged);
             }
+
+            serde_yaml::Value::Mapping(global)
         }
-        None => Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "Config file not found",
-        )),
+        (_, local) => local,
+    }
+}
+
+/// Load the config for the current working directory. The nearest
+/// local config file is merged over the global config file, so a
+/// local config only needs to set what differs from the global one.
+pub fn load_config() -> Result<Config, LicensureError> {
+    let mut warnings = Vec::new();
+    let paths: Vec<PathBuf> = vec![
+        find_global_config_file(&mut warnings),
+        find_local_config_file(&mut warnings),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if paths.is_empty() {
+        return Err(LicensureError::ConfigNotFound);
+    }
+
+    let mut config = load_config_files(&paths)?;
+    config.warnings = warnings;
+    Ok(config)
+}
+
+/// Load the config file at path instead of discovering one, the
+/// global config file is not merged in. A missing file is reported
+/// as an invalid config naming path rather than as not found.
+pub fn load_config_from(path: &Path) -> Result<Config, LicensureError> {
+    if !path.is_file() {
+        return Err(LicensureError::config(
+            path,
+            "the config file does not exist",
+        ));
+    }
+
+    load_config_files(&[path.to_path_buf()])
+}
+
+fn load_config_files(paths: &[PathBuf]) -> Result<Config, LicensureError> {
+    let mut merged = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+    for path in paths {
+        info!("using config file {}", path.display());
+        merged = merge_config_values(merged, read_config_value(path)?);
+    }
+
+    Config::from_value(merged).map_err(|e| LicensureError::Config {
+        paths: paths.to_vec(),
+        message: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_includes() {
+        let config: Config = serde_yaml::from_str("excludes: [.*\\.lock]\nlicenses: []").unwrap();
+        assert!(config.is_selected("src/main.rs"));
+        assert!(config.is_selected("docs/index.md"));
+        assert!(!config.is_selected("Cargo.lock"));
+
+        let config: Config = serde_yaml::from_str(
+            "includes: [src/.*, docs/.*]\nexcludes: [.*\\.lock]\nlicenses: []",
+        )
+        .unwrap();
+        assert!(config.is_selected("src/main.rs"));
+        assert!(config.is_selected("docs/index.md"));
+        assert!(!config.is_selected("build.rs"));
+        assert!(!config.is_selected("src/Cargo.lock"));
+    }
+
+    #[test]
+    fn test_excludes() {
+        let mut excludes: RegexList =
+            serde_yaml::from_str("[\"target/.*\", \".*\\\\.lock\"]").unwrap();
+        assert!(excludes.is_match("target/debug/licensure"));
+        assert!(excludes.is_match("Cargo.lock"));
+        assert!(!excludes.is_match("src/main.rs"));
+
+        excludes.add_exclude("src/.*").unwrap();
+        assert!(excludes.is_match("src/main.rs"));
+        assert!(excludes.is_match("Cargo.lock"));
+
+        let e = excludes.add_exclude("src/(").unwrap_err();
+        assert!(matches!(&e, LicensureError::Regex { pattern, .. } if pattern == "src/("));
+        assert!(e.to_string().contains("\"src/(\""));
+        assert!(excludes.is_match("Cargo.lock"));
+
+        let e = serde_yaml::from_str::<RegexList>("[\"a\", \"[b\"]")
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("\"[b\""));
+    }
+
+    #[test]
+    fn test_add_exclude_ext() {
+        let mut config: Config = serde_yaml::from_str("licenses: []").unwrap();
+        config.add_exclude_ext("lock").unwrap();
+        config.add_exclude_ext(".min.js").unwrap();
+        assert!(!config.is_selected("Cargo.lock"));
+        assert!(!config.is_selected("vendor/YARN.LOCK"));
+        assert!(!config.is_selected("static/app.min.js"));
+        assert!(config.is_selected("static/app.js"));
+        assert!(config.is_selected("src/block.rs"));
+        assert!(config.is_selected("src/lock"));
+        assert!(config.add_exclude_ext(" ").is_err());
+    }
+
+    #[test]
+    fn test_get_commenter_precedence() {
+        let go = "- extension: go\n  commenter: {type: line, comment_char: ';'}\n";
+        let any = "- extension: any\n  commenter: {type: line, comment_char: '#'}\n";
+        let comment = |comments: &str, file| {
+            let comments: CommentConfigList = serde_yaml::from_str(comments).unwrap();
+            comments.get_commenter(file).1.comment("text", None)
+        };
+
+        // Configurations apply in the order they are listed, "any"
+        // included, before any built-in style.
+        let any_first = format!("{}{}", any, go);
+        assert_eq!("# text\n", comment(&any_first, "main.go"));
+        assert_eq!("# text\n", comment(&any_first, "main.rs"));
+
+        let any_last = format!("{}{}", go, any);
+        assert_eq!("; text\n", comment(&any_last, "main.go"));
+        assert_eq!("# text\n", comment(&any_last, "main.rs"));
+        assert_eq!("# text\n", comment(&any_last, "unknown.xyz"));
+
+        // Built-in styles are used when nothing in the config matches.
+        assert_eq!("; text\n", comment(go, "main.go"));
+        assert_eq!("// text\n", comment(go, "main.rs"));
+    }
+
+    #[test]
+    fn test_line_ending_for_content() {
+        assert_eq!("\n", LineEnding::Auto.for_content("a\nb\n"));
+        assert_eq!("\r\n", LineEnding::Auto.for_content("a\r\nb\r\nc\n"));
+        assert_eq!("\n", LineEnding::Auto.for_content(""));
+        assert_eq!("\r\n", LineEnding::Crlf.for_content("a\nb\n"));
+    }
+
+    #[test]
+    fn test_inline_config() {
+        let config = Config::inline(InlineConfig {
+            template: "Copyright [name of author]\n".to_string(),
+            ident: "MIT".to_string(),
+            authors: vec!["Me <me@example.com>".to_string()],
+            comment_prefix: Some("--".to_string()),
+            columns: Some(80),
+        })
+        .unwrap();
+
+        let (cfg, commenter) = config.comments.get_commenter("src/main.rs");
+        assert_eq!(Some(80), cfg.get_columns());
+        let templ = futures::executor::block_on(config.get_template("src/main.rs"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            "-- Copyright Me <me@example.com>\n",
+            commenter.comment(&templ.render().unwrap(), None)
+        );
+    }
+
+    #[test]
+    fn test_token_map() {
+        let tokens: TokenMap = serde_yaml::from_str("{department: Legal}").unwrap();
+        assert_eq!(Some(&"Legal".to_string()), tokens.tokens.get("department"));
+
+        let err = serde_yaml::from_str::<TokenMap>("{year: \"1999\"}")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("[year] is built in"));
+        assert!(serde_yaml::from_str::<TokenMap>("{\"env:HOME\": x}").is_err());
+    }
+
+    #[test]
+    fn test_merge_config_values() {
+        let global: serde_yaml::Value = serde_yaml::from_str(
+            "change_in_place: true\nproject: {name: Global}\nexcludes: [a, b]\nlicenses: []",
+        )
+        .unwrap();
+        let local: serde_yaml::Value =
+            serde_yaml::from_str("change_in_place: false\nexcludes: [c]").unwrap();
+        let expected: serde_yaml::Value = serde_yaml::from_str(
+            "change_in_place: false\nproject: {name: Global}\nexcludes: [c]\nlicenses: []",
+        )
+        .unwrap();
+        assert_eq!(expected, merge_config_values(global, local));
+    }
+
+    #[test]
+    fn test_default_configs_match() {
+        #[derive(Deserialize)]
+        struct Defaults {
+            excludes: Vec<String>,
+            comments: Vec<serde_yaml::Value>,
+        }
+
+        let yaml: Defaults = serde_yaml::from_str(DEFAULT_CONFIG).unwrap();
+        let toml: Defaults = toml::from_str(DEFAULT_CONFIG_TOML).unwrap();
+        assert_eq!(yaml.excludes, toml.excludes);
+        assert_eq!(yaml.comments, toml.comments);
+    }
+
+    #[test]
+    fn test_load_config_from() {
+        let dir = TempDir::new("load-config-from");
+        let path = dir.join("licensure-test-config.yml");
+        fs::write(&path, "licenses: []\ncomments: []\n").unwrap();
+        let mut config = load_config_from(&path).unwrap();
+        let fingerprint = config.fingerprint();
+        config.set_ident("MIT");
+        assert_ne!(fingerprint, config.fingerprint());
+        fs::remove_file(&path).unwrap();
+
+        let err = load_config_from(&path).err().unwrap();
+        assert!(
+            matches!(err, LicensureError::Config { ref paths, .. } if paths.len() == 1 && paths[0] == path)
+        );
+        assert!(err.to_string().contains("licensure-test-config.yml"));
+    }
+
+    #[test]
+    fn test_find_config_file_from() {
+        let dir = TempDir::new("discovery");
+        let root = dir.path();
+        let nested = root.join("repo").join("src").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(".licensure.yml"), "").unwrap();
+        let mut warnings = Vec::new();
+        assert_eq!(
+            Some(root.join(".licensure.yml")),
+            find_config_file_from(nested.clone(), &mut warnings)
+        );
+
+        // The config above the repository is not used from inside it.
+        fs::create_dir_all(root.join("repo").join(".git")).unwrap();
+        assert_eq!(None, find_config_file_from(nested.clone(), &mut warnings));
+
+        fs::write(root.join("repo").join(".licensure.yml"), "").unwrap();
+        assert_eq!(
+            Some(root.join("repo").join(".licensure.yml")),
+            find_config_file_from(nested.clone(), &mut warnings)
+        );
+        assert!(warnings.is_empty());
+
+        // TOML is preferred when both exist, with a warning to tell.
+        fs::write(root.join("repo").join(".licensure.toml"), "").unwrap();
+        assert_eq!(
+            Some(root.join("repo").join(".licensure.toml")),
+            find_config_file_from(nested, &mut warnings)
+        );
+        assert_eq!(1, warnings.len());
+        assert!(warnings[0].contains(".licensure.yml"));
+    }
+
+    #[test]
+    fn test_template_file() {
+        let dir = TempDir::new("template-file");
+        fs::create_dir_all(dir.join("headers")).unwrap();
+        fs::write(
+            dir.join("headers").join("MIT.txt"),
+            "Licensed under [ident].\n",
+        )
+        .unwrap();
+        let config_path = dir.join(".licensure.yml");
+        let license = "licenses:\n  - files: any\n    ident: MIT\n    authors: []\n";
+        fs::write(
+            &config_path,
+            format!("{}    template_file: headers/MIT.txt\n", license),
+        )
+        .unwrap();
+
+        let config = load_config_from(&config_path).unwrap();
+        let templ = futures::executor::block_on(config.get_template("src/main.rs"))
+            .unwrap()
+            .unwrap();
+        assert_eq!("Licensed under MIT.\n", templ.render().unwrap());
+
+        fs::write(
+            &config_path,
+            format!("{}    template_file: headers/GPL.txt\n", license),
+        )
+        .unwrap();
+        let err = load_config_from(&config_path).err().unwrap();
+        assert!(matches!(err, LicensureError::Config { .. }));
+        assert!(err.to_string().contains("GPL.txt"));
+
+        fs::write(dir.join("headers").join("GPL.txt"), b"\xff\xfe").unwrap();
+        let err = load_config_from(&config_path).err().unwrap();
+        assert!(matches!(err, LicensureError::Config { .. }));
+        assert!(err.to_string().contains("not valid UTF-8"));
+
+        fs::write(
+            &config_path,
+            format!(
+                "{}    template: text\n    template_file: headers/MIT.txt\n",
+                license
+            ),
+        )
+        .unwrap();
+        assert!(load_config_from(&config_path).is_err());
+    }
+
+    #[test]
+    fn test_authors_file() {
+        let dir = TempDir::new("authors-file");
+        dir.write(
+            "AUTHORS",
+            "# The authors of this project\n\nJane Doe <jane@example.com>\n  John Smith\n",
+        );
+        let config_path = dir.join(".licensure.yml");
+        let license =
+            "licenses:\n  - files: any\n    ident: MIT\n    template: Copyright [name of author]\n";
+        fs::write(
+            &config_path,
+            format!("{}    authors_file: AUTHORS\n", license),
+        )
+        .unwrap();
+
+        let config = load_config_from(&config_path).unwrap();
+        let templ = futures::executor::block_on(config.get_template("src/main.rs"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            "Copyright Jane Doe <jane@example.com>, John Smith",
+            templ.render().unwrap()
+        );
+
+        fs::write(
+            &config_path,
+            format!("{}    authors: []\n    authors_file: AUTHORS\n", license),
+        )
+        .unwrap();
+        assert!(load_config_from(&config_path).is_err());
+
+        fs::write(
+            &config_path,
+            format!("{}    authors_file: CONTRIBUTORS\n", license),
+        )
+        .unwrap();
+        let err = load_config_from(&config_path).err().unwrap();
+        assert!(matches!(err, LicensureError::Config { ref paths, .. } if paths[0] == config_path));
+        assert!(err.to_string().contains("authors_file"));
+    }
+
+    #[test]
+    fn test_license_url() {
+        let config: Config = serde_yaml::from_str(concat!(
+            "license_urls:\n",
+            "  MIT: https://example.com/MIT\n",
+            "  Custom-1.0: https://example.com/custom\n",
+            "licenses: []\n",
+        ))
+        .unwrap();
+        assert_eq!(
+            Some("https://example.com/MIT".to_string()),
+            config.license_url("MIT")
+        );
+        assert_eq!(
+            Some("https://example.com/custom".to_string()),
+            config.license_url("Custom-1.0")
+        );
+        assert_eq!(
+            Some("https://www.apache.org/licenses/LICENSE-2.0".to_string()),
+            config.license_url("Apache-2.0")
+        );
+        assert_eq!(
+            Some("https://www.gnu.org/licenses/gpl-3.0.html".to_string()),
+            config.license_url("GPL-3.0-or-later")
+        );
+        assert_eq!(None, config.license_url("Proprietary"));
     }
 }
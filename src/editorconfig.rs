@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use globset::GlobBuilder;
+
+/// The name of the files editorconfig properties are read from.
+const EDITORCONFIG_FILE: &str = ".editorconfig";
+
+/// The indentation the .editorconfig files apply to file ask for, a
+/// tab or indent_size spaces. None when no indent_style applies or it
+/// is space without a size.
+pub fn indent(file: &Path) -> Option<String> {
+    let properties = properties(file);
+    match properties.get("indent_style").map(String::as_str) {
+        Some("tab") => Some("\t".to_string()),
+        Some("space") => {
+            let size = match properties.get("indent_size").map(String::as_str) {
+                Some("tab") => properties.get("tab_width")?,
+                size => size?,
+            };
+            Some(" ".repeat(size.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+/// The properties of the sections matching file in the .editorconfig
+/// files of its directory and those above it, up to the first one with
+/// root = true. Keys and values are lowercased, and closer files and
+/// later sections win.
+fn properties(file: &Path) -> HashMap<String, String> {
+    let file = match env::current_dir() {
+        Ok(cwd) => cwd.join(file),
+        Err(_) => file.to_path_buf(),
+    };
+
+    let mut configs: Vec<(PathBuf, String)> = Vec::new();
+    for dir in file.ancestors().skip(1) {
+        let content = match fs::read_to_string(dir.join(EDITORCONFIG_FILE)) {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+        let is_root = parse(&content)
+            .into_iter()
+            .any(|(section, key, value)| section.is_none() && key == "root" && value == "true");
+        configs.push((dir.to_path_buf(), content));
+        if is_root {
+            break;
+        }
+    }
+
+    let mut properties = HashMap::new();
+    for (dir, content) in configs.iter().rev() {
+        let relative = match file.strip_prefix(dir) {
+            Ok(relative) => relative,
+            Err(_) => continue,
+        };
+        for (section, key, value) in parse(content) {
+            if section.is_some_and(|pattern| section_matches(pattern, relative)) {
+                properties.insert(key, value);
+            }
+        }
+    }
+
+    properties
+}
+
+/// The properties of an .editorconfig file along with the pattern of
+/// their section, None for those before the first section.
+fn parse(content: &str) -> Vec<(Option<&str>, String, String)> {
+    let mut section = None;
+    let mut properties = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+
+        if let Some(pattern) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(pattern);
+        } else if let Some((key, value)) = line.split_once('=') {
+            properties.push((
+                section,
+                key.trim().to_lowercase(),
+                value.trim().to_lowercase(),
+            ));
+        }
+    }
+
+    properties
+}
+
+/// Whether the section pattern matches file, relative to the
+/// directory of the .editorconfig. Patterns without a slash match
+/// files of that name in any directory.
+fn section_matches(pattern: &str, file: &Path) -> bool {
+    let pattern = if pattern.contains('/') {
+        pattern.trim_start_matches('/').to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+
+    match GlobBuilder::new(&pattern).literal_separator(true).build() {
+        Ok(glob) => glob.compile_matcher().is_match(file),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_indent() {
+        let dir = TempDir::new("editorconfig");
+        dir.write(
+            EDITORCONFIG_FILE,
+            "root = true\n\n[*]\nindent_style = space\nindent_size = 4\n\n[*.{c,h}]\nindent_style = tab\n\n[Makefile]\nindent_style = space\nindent_size = tab\n",
+        );
+        dir.write("src/.editorconfig", "[*.css]\nindent_size = 2\n");
+
+        assert_eq!(Some("\t".to_string()), indent(&dir.join("src/main.c")));
+        assert_eq!(Some("    ".to_string()), indent(&dir.join("main.css")));
+        assert_eq!(Some("  ".to_string()), indent(&dir.join("src/main.css")));
+        assert_eq!(None, indent(&dir.join("Makefile")));
+        assert_eq!(None, indent(&env::temp_dir().join("licensure-test.c")));
+    }
+}
@@ -0,0 +1,130 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// The errors of loading a config and licensing files.
+#[derive(Debug)]
+pub enum LicensureError {
+    /// No config file was found for the current working directory.
+    ConfigNotFound,
+    /// The config is not valid. paths are the config files it was read
+    /// from, none when it was built from the command line.
+    Config {
+        paths: Vec<PathBuf>,
+        message: String,
+    },
+    /// pattern, an exclude or include, is not a valid regex.
+    Regex {
+        pattern: String,
+        error: regex::Error,
+    },
+    /// No comment style is known for these files, found by strict runs
+    /// before any file is licensed.
+    UnknownExtension(Vec<String>),
+    /// The license template for ident cannot be rendered, such as for
+    /// using a token with no value configured.
+    Template { ident: String, message: String },
+    /// Reading or writing a file failed.
+    Io(io::Error),
+}
+
+impl LicensureError {
+    /// An error of the config read from the file at path.
+    pub(crate) fn config(path: impl Into<PathBuf>, message: impl fmt::Display) -> LicensureError {
+        LicensureError::Config {
+            paths: vec![path.into()],
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LicensureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LicensureError::ConfigNotFound => write!(f, "Config file not found"),
+            LicensureError::Config { paths, message } if paths.is_empty() => {
+                write!(f, "Invalid command line config: {}", message)
+            }
+            LicensureError::Config { paths, message } => {
+                let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "Invalid config in {}: {}", paths.join(" and "), message)
+            }
+            LicensureError::Regex { pattern, error } => {
+                write!(f, "Invalid pattern {:?}: {}", pattern, error)
+            }
+            LicensureError::UnknownExtension(files) => write!(
+                f,
+                "no comment style is known for the following files, so no file was licensed:\n{}",
+                files.join("\n")
+            ),
+            LicensureError::Template { ident, message } => write!(
+                f,
+                "Unable to render the license template for {}: {}",
+                ident, message
+            ),
+            LicensureError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for LicensureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LicensureError::Regex { error, .. } => Some(error),
+            LicensureError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LicensureError {
+    fn from(e: io::Error) -> LicensureError {
+        LicensureError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let err = LicensureError::Config {
+            paths: Vec::new(),
+            message: "missing field `ident`".to_string(),
+        };
+        assert_eq!(
+            "Invalid command line config: missing field `ident`",
+            err.to_string()
+        );
+
+        let err = LicensureError::Config {
+            paths: vec![PathBuf::from("global.yml"), PathBuf::from(".licensure.yml")],
+            message: "missing field `ident`".to_string(),
+        };
+        assert_eq!(
+            "Invalid config in global.yml and .licensure.yml: missing field `ident`",
+            err.to_string()
+        );
+        assert!(err.source().is_none());
+
+        let pattern = "(".to_string();
+        let err = LicensureError::Regex {
+            error: regex::Regex::new(&pattern).unwrap_err(),
+            pattern,
+        };
+        assert!(err.to_string().starts_with("Invalid pattern \"(\": "));
+        assert!(err.source().is_some());
+
+        let err = LicensureError::Template {
+            ident: "MIT".to_string(),
+            message: "the [project] token has no value".to_string(),
+        };
+        assert_eq!(
+            "Unable to render the license template for MIT: the [project] token has no value",
+            err.to_string()
+        );
+        assert!(err.source().is_none());
+    }
+}
@@ -0,0 +1,105 @@
+use std::ops::Range;
+
+/// The key of the frontmatter entry holding the license header.
+const COPYRIGHT_KEY: &str = "copyright:";
+
+/// Whether file is a Markdown file, whose header goes in its YAML
+/// frontmatter when it has one.
+pub fn is_markdown(file: &str) -> bool {
+    file.ends_with(".md") || file.ends_with(".markdown")
+}
+
+/// The byte range of the YAML frontmatter at the start of content,
+/// between the opening --- line and the closing --- or ... line.
+pub fn find(content: &str) -> Option<Range<usize>> {
+    let mut lines = content.split_inclusive('\n');
+    let opening = lines.next()?;
+    if opening.trim_end_matches(['\n', '\r']) != "---" {
+        return None;
+    }
+
+    let mut offset = opening.len();
+    for line in lines {
+        let delimiter = line.trim_end_matches(['\n', '\r']);
+        if delimiter == "---" || delimiter == "..." {
+            return Some(opening.len()..offset);
+        }
+        offset += line.len();
+    }
+
+    None
+}
+
+/// The byte range of the copyright entry in frontmatter, its key line
+/// and any indented lines of its value which follow.
+pub fn find_copyright(frontmatter: &str) -> Option<Range<usize>> {
+    let mut start = None;
+    let mut end = 0;
+    let mut offset = 0;
+    for line in frontmatter.split_inclusive('\n') {
+        let next = offset + line.len();
+        match start {
+            None if line.starts_with(COPYRIGHT_KEY) => {
+                start = Some(offset);
+                end = next;
+            }
+            None => (),
+            Some(_) if line.starts_with([' ', '\t']) => end = next,
+            Some(_) if line.trim().is_empty() => (),
+            Some(_) => break,
+        }
+        offset = next;
+    }
+
+    start.map(|start| start..end)
+}
+
+/// Render header as a copyright entry using a literal block scalar so
+/// that it needs no quoting.
+pub fn copyright_entry(header: &str, line_ending: &str) -> String {
+    let mut entry = format!("{} |{}", COPYRIGHT_KEY, line_ending);
+    for line in header.trim_end().lines() {
+        if !line.is_empty() {
+            entry.push_str("  ");
+            entry.push_str(line);
+        }
+        entry.push_str(line_ending);
+    }
+
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOC: &str = "---\ntitle: Docs\ncopyright: |\n  Copyright 2019 Me\n\n  All rights reserved.\ntags: [a]\n---\n# Docs\n";
+
+    #[test]
+    fn test_find() {
+        let range = find(DOC).unwrap();
+        assert!(DOC[range.clone()].starts_with("title: Docs\n"));
+        assert!(DOC[range].ends_with("tags: [a]\n"));
+        assert_eq!(None, find("# Docs\n---\n"));
+        assert_eq!(None, find("---\nunterminated: true\n"));
+    }
+
+    #[test]
+    fn test_find_copyright() {
+        let frontmatter = &DOC[find(DOC).unwrap()];
+        let range = find_copyright(frontmatter).unwrap();
+        assert_eq!(
+            "copyright: |\n  Copyright 2019 Me\n\n  All rights reserved.\n",
+            &frontmatter[range]
+        );
+        assert_eq!(None, find_copyright("title: Docs\n"));
+    }
+
+    #[test]
+    fn test_copyright_entry() {
+        assert_eq!(
+            "copyright: |\n  Copyright 2019 Me\n\n  All rights reserved.\n",
+            copyright_entry("Copyright 2019 Me\n\nAll rights reserved.\n", "\n")
+        );
+    }
+}
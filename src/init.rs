@@ -0,0 +1,162 @@
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::Command;
+
+use licensure::config::DEFAULT_CONFIG;
+
+/// The file licensure init writes.
+const CONFIG_FILE: &str = ".licensure.yml";
+
+/// What licensure init asks for.
+struct Answers {
+    ident: String,
+    name: String,
+    email: Option<String>,
+    columns: Option<usize>,
+}
+
+/// Ask for the license, author, and columns to use and write a config
+/// file tailored to them. When stdin is not a terminal nothing is asked
+/// and the default config is written instead.
+pub fn run() -> io::Result<()> {
+    let config = if io::stdin().is_terminal() {
+        let answers = ask(&mut io::stdin().lock())?;
+        tailored_config(&answers)
+    } else {
+        eprintln!("Not running in a terminal, writing the default config.");
+        DEFAULT_CONFIG.to_string()
+    };
+
+    let mut f = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(CONFIG_FILE)
+        .map_err(|e| {
+            io::Error::new(e.kind(), format!("Unable to create {}: {}", CONFIG_FILE, e))
+        })?;
+    f.write_all(config.as_bytes())?;
+    eprintln!("Wrote {}", CONFIG_FILE);
+    Ok(())
+}
+
+fn ask(input: &mut impl BufRead) -> io::Result<Answers> {
+    let ident = prompt(input, "License identifier", Some("MIT"))?;
+    let name = loop {
+        let name = prompt(input, "Author name", git_config("user.name").as_deref())?;
+        if !name.is_empty() {
+            break name;
+        }
+    };
+    let email = Some(prompt(
+        input,
+        "Author email",
+        git_config("user.email").as_deref(),
+    )?)
+    .filter(|email| !email.is_empty());
+    let columns = loop {
+        let columns = prompt(
+            input,
+            "Wrap headers at column (empty for no wrapping)",
+            None,
+        )?;
+        if columns.is_empty() {
+            break None;
+        }
+        match columns.parse() {
+            Ok(n) => break Some(n),
+            Err(_) => eprintln!("{} is not a number", columns),
+        }
+    };
+
+    Ok(Answers {
+        ident,
+        name,
+        email,
+        columns,
+    })
+}
+
+/// Ask question on stderr and read the answer from input, the default
+/// when the answer is empty. A closed input is an error so that init
+/// never waits for answers which cannot come.
+fn prompt(input: &mut impl BufRead, question: &str, default: Option<&str>) -> io::Result<String> {
+    match default {
+        Some(default) => eprint!("{} [{}]: ", question, default),
+        None => eprint!("{}: ", question),
+    }
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "no answer was given",
+        ));
+    }
+
+    let answer = answer.trim();
+    Ok(match default {
+        Some(default) if answer.is_empty() => default.to_string(),
+        _ => answer.to_string(),
+    })
+}
+
+/// The value of the git config key, None when git or the key is
+/// missing.
+fn git_config(key: &str) -> Option<String> {
+    let output = Command::new("git").args(["config", key]).output().ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|value| output.status.success() && !value.is_empty())
+}
+
+/// The default config with a license for answers and the comment
+/// columns filled in.
+fn tailored_config(answers: &Answers) -> String {
+    let mut license = format!(
+        "licenses:\n  - files: any\n    ident: {}\n    authors:\n      - name: {}\n",
+        yaml_string(&answers.ident),
+        yaml_string(&answers.name)
+    );
+    if let Some(email) = &answers.email {
+        license.push_str(&format!("        email: {}\n", yaml_string(email)));
+    }
+    license.push_str(concat!(
+        "    template: |\n",
+        "      Copyright [year] [name of author]. All rights reserved. Use of\n",
+        "      this source code is governed by the [ident] license that can be\n",
+        "      found in the LICENSE file.\n",
+    ));
+
+    let mut config = DEFAULT_CONFIG.replacen("\nlicenses:\n", &format!("\n{}", license), 1);
+    if let Some(columns) = answers.columns {
+        config = config.replace(
+            "\n    commenter:\n",
+            &format!("\n    columns: {}\n    commenter:\n", columns),
+        );
+    }
+
+    config
+}
+
+/// Quote s for YAML so that names like "Jane: Doe" stay strings.
+fn yaml_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tailored_config() {
+        let mut input = "GPL-3.0\nJane Doe\njane@example.com\nabc\n80\n".as_bytes();
+        let answers = ask(&mut input).unwrap();
+        let config: licensure::Config = serde_yaml::from_str(&tailored_config(&answers)).unwrap();
+        let (cfg, commenter) = config.comments.get_commenter("main.go");
+        assert_eq!(Some(80), cfg.get_columns());
+        assert_eq!("// text\n", commenter.comment("text", None));
+
+        let mut input = "".as_bytes();
+        assert!(ask(&mut input).is_err());
+    }
+}
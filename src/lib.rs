@@ -0,0 +1,42 @@
+// Copyright 2018 Mathew Robinson <chasinglogic@gmail.com>. All rights reserved.
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Licensure adds, checks, and updates the license headers of source
+//! files. The licensure binary is a thin command line wrapper over
+//! this library.
+
+#[macro_use]
+extern crate log;
+
+#[cfg(feature = "archives")]
+mod archive;
+mod cache;
+pub mod comments;
+pub mod config;
+mod editorconfig;
+mod error;
+mod frontmatter;
+mod git;
+mod licensure;
+mod notebook;
+pub mod template;
+#[cfg(test)]
+mod testing;
+
+pub use crate::cache::CACHE_FILE;
+pub use crate::config::Config;
+pub use crate::error::LicensureError;
+pub use crate::licensure::{FailOn, FileError, Licensure, Report};
+pub use crate::template::Template;
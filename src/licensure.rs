@@ -1,71 +1,2430 @@
-use std::fs::File;
-use std::io;
-use std::io::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, BufRead, Read};
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
 
-use crate::config::Config;
+use futures::executor::block_on;
+use regex::Regex;
+use serde::Serialize;
+use similar::TextDiff;
+
+#[cfg(feature = "archives")]
+use crate::archive::Archive;
+use crate::cache::{Cache, Entry, Stamp};
+use crate::comments::Comment;
+use crate::config::{Config, RegexList};
+use crate::error::LicensureError;
+use crate::frontmatter;
+use crate::git;
+use crate::notebook::{self, Cell};
+use crate::template::Template;
+
+const UTF8_BOM: &str = "\u{feff}";
+
+/// How many files may wait for each worker, which bounds the memory
+/// used by license_stream however many files it is given.
+const QUEUED_PER_JOB: usize = 16;
+
+/// How many bytes are read at most looking for the shebang of a file.
+const FIRST_LINE_LIMIT: u64 = 1024;
+
+/// What happened to a single file.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Outcome {
+    /// The file already had the expected header.
+    AlreadyLicensed,
+    /// The file was given, or during a dry run would be given, a header.
+    Licensed,
+    /// The file has the expected header but the lines after it differ.
+    TrailingMismatch,
+    /// The file has a header which only differs by its year or authors.
+    Outdated,
+    /// The outdated header of the file was replaced.
+    Updated,
+    /// The file matched an exclude pattern.
+    Excluded,
+    /// No license configuration matched the file.
+    Unmatched,
+    /// The format of the file does not support comments.
+    Uncommentable,
+    /// The file has a copyright notice other than ours.
+    Foreign,
+    /// The file contains the opt out marker.
+    OptedOut,
+    /// The file has more than one license header.
+    Duplicated,
+    /// The license headers after the first were removed from the file.
+    Deduplicated,
+    /// The file has a header, though not the expected one, which was
+    /// left as is as only missing headers are added, or the file has no
+    /// header to reflow when only reflowing headers.
+    Kept,
+    /// The header only differed from the expected one in its wrapping
+    /// or spacing and was rewritten.
+    Reflowed,
+}
+
+impl Outcome {
+    /// Whether the file did not already have the expected header.
+    fn not_licensed(self) -> bool {
+        matches!(
+            self,
+            Outcome::Licensed
+                | Outcome::TrailingMismatch
+                | Outcome::Outdated
+                | Outcome::Updated
+                | Outcome::Duplicated
+                | Outcome::Deduplicated
+                | Outcome::Reflowed
+        )
+    }
+}
+
+/// Which files make a check fail.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FailOn {
+    /// Files with a header which only differs by its year or authors.
+    Outdated,
+    /// Files without a license header.
+    Missing,
+    /// Any file which does not have the expected header.
+    Any,
+}
+
+/// The result of processing a single file.
+struct FileResult {
+    outcome: Outcome,
+    /// Text to print for this file, printed in input order once all
+    /// files have been processed, or its diff when writing a patch.
+    output: Option<String>,
+    /// The cache entry recording that the file has the expected header.
+    cache_entry: Option<Entry>,
+}
+
+impl FileResult {
+    fn new(outcome: Outcome, output: Option<String>) -> FileResult {
+        FileResult {
+            outcome,
+            output,
+            cache_entry: None,
+        }
+    }
+
+    fn set_cache_entry(mut self, entry: Option<Entry>) -> FileResult {
+        self.cache_entry = entry;
+        self
+    }
+}
+
+/// The result of licensing a file, or of each text entry when it is an
+/// archive, along with its name.
+type PathResults = Vec<(String, FileResult)>;
+
+/// Called with each file once it has been processed, along with the
+/// number of files processed so far and the total number of files, None
+/// while files are still being found.
+type ProgressCallback = Box<dyn Fn(&str, usize, Option<usize>) + Send + Sync>;
+
+/// Called with the text produced for each file, in the order of files.
+type OutputCallback = Box<dyn Fn(&str) + Send + Sync>;
+
+/// A file which could not be licensed, such as one which cannot be
+/// read, along with why.
+#[derive(Serialize)]
+pub struct FileError {
+    pub file: String,
+    pub error: String,
+}
+
+/// The outcome of licensing a set of files.
+#[derive(Default, Serialize)]
+pub struct Report {
+    /// Files which did not have the expected license header, including
+    /// those which were licensed or updated by this run.
+    pub not_licensed: Vec<String>,
+    /// Files which had no license header, including those which were
+    /// licensed by this run.
+    pub missing: Vec<String>,
+    /// Files with a header which only differs from the expected one by
+    /// its year or authors.
+    pub outdated: Vec<String>,
+    /// Outdated files whose header was replaced.
+    pub updated: Vec<String>,
+    /// Files with more than one license header, including those which
+    /// were deduplicated by this run.
+    pub duplicated: Vec<String>,
+    /// Duplicated files whose later license headers were removed.
+    pub deduplicated: Vec<String>,
+    /// Files whose header only differed from the expected one in its
+    /// wrapping or spacing, and was rewritten, when reflowing headers.
+    pub reflowed: Vec<String>,
+    /// Files skipped because they already have a different copyright
+    /// notice, only used with skip_if_any_copyright.
+    pub foreign: Vec<String>,
+    /// The number of files which were given a header by this run.
+    pub newly_licensed: usize,
+    /// The number of files which already had the expected header.
+    pub licensed: usize,
+    /// The number of files which matched an exclude pattern.
+    pub excluded: usize,
+    /// The number of files which matched no license configuration.
+    pub unmatched: usize,
+    /// The number of files skipped as their format does not support
+    /// comments, such as JSON.
+    pub uncommentable: usize,
+    /// The number of files skipped as they contain the opt out marker.
+    pub opted_out: usize,
+    /// The number of files whose header was left as is although it is
+    /// not the expected one, only used when adding headers only or
+    /// reflowing them.
+    pub kept: usize,
+    /// Files which could not be licensed, the other files are still
+    /// licensed as usual.
+    pub errors: Vec<FileError>,
+}
+
+impl Report {
+    /// Count file under the outcome of licensing it.
+    fn record(&mut self, file: &str, outcome: Outcome) {
+        if outcome.not_licensed() {
+            self.not_licensed.push(file.to_string());
+        }
+
+        match outcome {
+            Outcome::AlreadyLicensed => self.licensed += 1,
+            Outcome::Licensed => {
+                self.missing.push(file.to_string());
+                self.newly_licensed += 1;
+            }
+            Outcome::TrailingMismatch => (),
+            Outcome::Outdated => self.outdated.push(file.to_string()),
+            Outcome::Updated => {
+                self.outdated.push(file.to_string());
+                self.updated.push(file.to_string());
+            }
+            Outcome::Excluded => self.excluded += 1,
+            Outcome::Unmatched => self.unmatched += 1,
+            Outcome::Uncommentable => self.uncommentable += 1,
+            Outcome::Foreign => self.foreign.push(file.to_string()),
+            Outcome::OptedOut => self.opted_out += 1,
+            Outcome::Duplicated => self.duplicated.push(file.to_string()),
+            Outcome::Deduplicated => {
+                self.duplicated.push(file.to_string());
+                self.deduplicated.push(file.to_string());
+            }
+            Outcome::Kept => self.kept += 1,
+            Outcome::Reflowed => self.reflowed.push(file.to_string()),
+        }
+    }
+
+    /// The files which were given a header, updated, deduplicated, or
+    /// reflowed,
+    /// that is those this run changed, or would have when not changing
+    /// them in place.
+    pub fn changed(&self) -> Vec<&str> {
+        self.missing
+            .iter()
+            .chain(&self.updated)
+            .chain(&self.deduplicated)
+            .chain(&self.reflowed)
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Whether a check of the files fails for fail_on.
+    pub fn fails(&self, fail_on: FailOn) -> bool {
+        match fail_on {
+            FailOn::Outdated => !self.outdated.is_empty(),
+            FailOn::Missing => !self.missing.is_empty(),
+            FailOn::Any => !self.not_licensed.is_empty(),
+        }
+    }
+
+    /// A one line summary of the number of files with each outcome.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![
+            format!("Licensed: {}", self.newly_licensed),
+            format!("Already licensed: {}", self.licensed),
+        ];
+
+        let optional = [
+            ("Updated", self.updated.len()),
+            ("Reflowed", self.reflowed.len()),
+            ("Outdated", self.outdated.len() - self.updated.len()),
+            ("Left as is", self.kept),
+            ("Deduplicated", self.deduplicated.len()),
+            (
+                "Duplicated",
+                self.duplicated.len() - self.deduplicated.len(),
+            ),
+            ("Skipped (foreign license)", self.foreign.len()),
+            ("Skipped (excluded)", self.excluded),
+            ("Skipped (opted out)", self.opted_out),
+            ("Skipped (no license config)", self.unmatched),
+            ("Skipped (no comment syntax)", self.uncommentable),
+            ("Failed", self.errors.len()),
+        ];
+        for (name, count) in optional.iter() {
+            if *count > 0 {
+                parts.push(format!("{}: {}", name, count));
+            }
+        }
+
+        format!("{}.", parts.join(", "))
+    }
+}
 
 pub struct Licensure {
     config: Config,
+    print_diff: bool,
+    patch_file: Option<String>,
+    update: bool,
+    add_only: bool,
+    normalize: bool,
+    strict: bool,
+    dedupe: bool,
+    dry_run: bool,
+    jobs: usize,
+    backup_suffix: Option<String>,
+    cache_file: Option<String>,
+    cache: Cache,
+    progress: Option<ProgressCallback>,
+    output: Option<OutputCallback>,
+    /// The compiled outdated and reflow header patterns by their
+    /// source, most files share the pattern of their comment style and
+    /// columns.
+    outdated_patterns: Mutex<HashMap<String, Regex>>,
 }
 
 impl Licensure {
     pub fn new(config: Config) -> Licensure {
-        Licensure { config }
+        Licensure {
+            config,
+            print_diff: false,
+            patch_file: None,
+            update: false,
+            add_only: false,
+            normalize: false,
+            strict: false,
+            dedupe: false,
+            dry_run: false,
+            jobs: default_jobs(),
+            backup_suffix: None,
+            cache_file: None,
+            cache: Cache::default(),
+            progress: None,
+            output: None,
+            outdated_patterns: Mutex::new(HashMap::new()),
+        }
     }
 
-    pub async fn license_files(self, files: &[String]) -> Result<Vec<&String>, io::Error> {
-        let mut files_not_licensed = Vec::new();
-        for file in files {
-            if self.config.excludes.is_match(file) {
-                continue;
+    /// When set a unified diff of the changes licensing would make is
+    /// printed for each file instead of the licensed file content.
+    pub fn set_print_diff(mut self, yes_or_no: bool) -> Licensure {
+        self.print_diff = yes_or_no;
+        self
+    }
+
+    /// When set no file is changed or printed, instead the diffs of the
+    /// changes licensing would make to every file are written to path
+    /// as a single patch which git apply takes. Paths in the patch are
+    /// relative to the root of the repository, and archive entries are
+    /// left out as they cannot be patched. This takes precedence over
+    /// set_print_diff and set_dry_run.
+    pub fn set_patch_file(mut self, path: Option<String>) -> Licensure {
+        self.patch_file = path;
+        self
+    }
+
+    /// When set headers which only differ from the rendered template
+    /// by their year or authors are replaced with the current header.
+    pub fn set_update(mut self, yes_or_no: bool) -> Licensure {
+        self.update = yes_or_no;
+        self
+    }
+
+    /// When set headers are only added to files which have none, any
+    /// file with a header, even an outdated or different one, is left
+    /// as is. This takes precedence over set_update and set_dedupe.
+    pub fn set_add_only(mut self, yes_or_no: bool) -> Licensure {
+        self.add_only = yes_or_no;
+        self
+    }
+
+    /// When set only headers which differ from the expected one in
+    /// nothing but their wrapping or spacing are changed, they are
+    /// rewritten to the expected header. Files without such a header
+    /// are left as is, whatever their year or authors.
+    pub fn set_normalize(mut self, yes_or_no: bool) -> Licensure {
+        self.normalize = yes_or_no;
+        self
+    }
+
+    /// When set any file without a known comment style, which would be
+    /// commented with the default # otherwise, fails the run before any
+    /// file is licensed or changed. The error lists all such files.
+    pub fn set_strict(mut self, yes_or_no: bool) -> Licensure {
+        self.strict = yes_or_no;
+        self
+    }
+
+    /// When set files with more than one license header keep only the
+    /// first, the later headers are removed.
+    pub fn set_dedupe(mut self, yes_or_no: bool) -> Licensure {
+        self.dedupe = yes_or_no;
+        self
+    }
+
+    /// When set no file is modified, instead what would be done to
+    /// each file is printed along with the header it would get.
+    pub fn set_dry_run(mut self, yes_or_no: bool) -> Licensure {
+        self.dry_run = yes_or_no;
+        self
+    }
+
+    /// Set the number of files which are processed concurrently.
+    pub fn set_jobs(mut self, jobs: usize) -> Licensure {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// When set the original content of each file changed in place is
+    /// first copied to the file name followed by suffix, e.g. .orig.
+    /// Files ending with suffix are excluded so that backups are never
+    /// licensed themselves.
+    pub fn set_backup_suffix(mut self, suffix: Option<String>) -> Licensure {
+        self.backup_suffix = suffix;
+        self
+    }
+
+    /// When set files which had the expected header on an earlier run
+    /// with the same cache file, and have not changed since, are not
+    /// read again. The cache is discarded whenever the config changes.
+    pub fn set_cache_file(mut self, path: Option<String>) -> Licensure {
+        self.cache_file = path;
+        self
+    }
+
+    /// Call progress with each file once it has been processed, along
+    /// with the number of files processed so far and the total. Files
+    /// are processed concurrently so progress is called from several
+    /// threads and may see files out of order. When files are streamed
+    /// the total is None until every file has been found, so that it
+    /// never changes once given.
+    pub fn set_progress<F>(mut self, progress: F) -> Licensure
+    where
+        F: Fn(&str, usize, Option<usize>) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Call output with the text produced for each file, in the order
+    /// of files once those before it are done: its licensed content
+    /// when not changing files in place, its diff when printing diffs,
+    /// or what a dry run would do to it. Without it the text is
+    /// dropped, only the returned Report tells what happened.
+    pub fn set_output<F>(mut self, output: F) -> Licensure
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.output = Some(Box::new(output));
+        self
+    }
+
+    pub async fn license_files(self, files: &[String]) -> Result<Report, LicensureError> {
+        self.license_stream(files.iter().cloned()).await
+    }
+
+    /// License files as they are produced, so that licensing starts
+    /// while a directory is still being walked. Only a few files per
+    /// job wait for a worker at any time, and like license_files the
+    /// results are printed and reported in the order of files. When
+    /// strict all files are first checked to have a comment style, so
+    /// that they are only licensed once all of them are found.
+    pub async fn license_stream<I>(self, files: I) -> Result<Report, LicensureError>
+    where
+        I: Iterator<Item = String> + Send,
+    {
+        if !self.strict {
+            return self.stream(files).await;
+        }
+
+        let files: Vec<String> = files.collect();
+        let unhandled: Vec<String> = files
+            .iter()
+            .filter(|file| self.is_unhandled(file))
+            .cloned()
+            .collect();
+        if !unhandled.is_empty() {
+            return Err(LicensureError::UnknownExtension(unhandled));
+        }
+
+        self.stream(files.into_iter()).await
+    }
+
+    /// Whether file would be licensed with the default comment style
+    /// as none is known for it. Archives and notebooks are licensed
+    /// by their content and are handled whatever their name.
+    fn is_unhandled(&self, file: &str) -> bool {
+        !is_archive(file)
+            && !notebook::is_notebook(file)
+            && self.excluded(file, file).is_none()
+            && !self.config.comments.has_commenter(&self.commented_as(file))
+    }
+
+    /// The name file is commented as, see
+    /// CommentConfigList::commented_as.
+    fn commented_as(&self, file: &str) -> String {
+        self.config.comments.commented_as(file, || first_line(file))
+    }
+
+    async fn stream<I>(mut self, files: I) -> Result<Report, LicensureError>
+    where
+        I: Iterator<Item = String> + Send,
+    {
+        if let Some(path) = &self.cache_file {
+            self.cache = Cache::load(path, self.config.fingerprint());
+        }
+
+        let total = match files.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(lower),
+            _ => None,
+        };
+        let workers = match files.size_hint() {
+            (_, Some(upper)) => self.jobs.min(upper),
+            _ => self.jobs,
+        }
+        .max(1);
+
+        let found = AtomicUsize::new(0);
+        let listed = AtomicBool::new(false);
+        let completed = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+        let (queue, queued) = mpsc::sync_channel::<(usize, String)>(workers * QUEUED_PER_JOB);
+        let queued = Mutex::new(queued);
+        let (finish, finished) = mpsc::channel();
+
+        let mut report = Report::default();
+        let mut patch = String::new();
+        let mut cache_updates = Vec::new();
+        let mut error = None;
+        let this = &self;
+        thread::scope(|s| {
+            s.spawn(|| {
+                for (idx, file) in files.enumerate() {
+                    found.fetch_add(1, Ordering::SeqCst);
+                    if queue.send((idx, file)).is_err() {
+                        break;
+                    }
+                }
+                listed.store(true, Ordering::SeqCst);
+                drop(queue);
+            });
+
+            for _ in 0..workers {
+                let finish = finish.clone();
+                let (found, listed, completed, failed, queued) =
+                    (&found, &listed, &completed, &failed, &queued);
+                s.spawn(move || loop {
+                    let next = queued.lock().expect("licensing queue poisoned").recv();
+                    let (idx, file) = match next {
+                        Ok(next) => next,
+                        Err(_) => break,
+                    };
+                    // After a failure the remaining files are only
+                    // drained so that the producer is not blocked.
+                    if failed.load(Ordering::SeqCst) {
+                        continue;
+                    }
+
+                    let result = block_on(this.license_path(&file)).map_err(|e| (file.clone(), e));
+                    if let Some(progress) = &this.progress {
+                        let count = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                        let total = total.or_else(|| {
+                            listed
+                                .load(Ordering::SeqCst)
+                                .then(|| found.load(Ordering::SeqCst))
+                        });
+                        progress(&file, count, total);
+                    }
+                    if finish.send((idx, result)).is_err() {
+                        break;
+                    }
+                });
             }
+            drop(finish);
 
-            let templ = match self.config.licenses.get_template(file).await {
-                Some(t) => t,
-                None => {
-                    info!("skipping {} because no license config matched.", file);
+            // Workers finish in any order, hold results back until
+            // those of every earlier file are in so that output and
+            // the reported files are deterministic.
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+            for (idx, result) in finished {
+                match result {
+                    Ok(results) => {
+                        pending.insert(idx, results);
+                    }
+                    // Failing to license one file does not stop the
+                    // others from being licensed.
+                    Err((file, e)) if is_file_error(&e) => {
+                        info!("failed to license {}: {}", file, e);
+                        pending.insert(idx, Vec::new());
+                        report.errors.push(FileError {
+                            file,
+                            error: e.to_string(),
+                        });
+                    }
+                    Err((_, e)) => {
+                        failed.store(true, Ordering::SeqCst);
+                        error.get_or_insert(e);
+                    }
+                }
+                if error.is_some() {
                     continue;
                 }
-            };
 
-            let uncommented = templ.render();
-            let (cfg, commenter) = self.config.comments.get_commenter(file);
-            let mut header = commenter.comment(&uncommented, cfg.get_columns());
-            let mut content = String::new();
-            {
-                let mut f = File::open(file)?;
-                f.read_to_string(&mut content)?;
+                while let Some(results) = pending.remove(&next) {
+                    next += 1;
+                    for (file, result) in results {
+                        if let Some(output) = &result.output {
+                            if this.patch_file.is_some() {
+                                patch.push_str(output);
+                            } else if let Some(print) = &this.output {
+                                print(output);
+                            }
+                        }
+
+                        report.record(&file, result.outcome);
+                        cache_updates.push((file, result.cache_entry));
+                    }
+                }
             }
+        });
 
-            // TODO: make this smarter about updating years etc.
-            if content.contains(&header) {
-                info!("{} already licensed", file);
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        for (file, entry) in cache_updates {
+            self.cache.update(&file, entry);
+        }
+
+        if let Some(path) = &self.cache_file {
+            if let Err(e) = self.cache.save(path) {
+                warn!("Unable to write the cache to {}: {}", path, e);
+            }
+        }
+
+        if let Some(path) = &self.patch_file {
+            fs::write(path, &patch).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("Unable to write the patch to {}: {}", path, e),
+                )
+            })?;
+        }
+
+        Ok(report)
+    }
+
+    /// License file, or each text entry of file when it is an archive,
+    /// returning the result for each along with its name.
+    async fn license_path(&self, file: &str) -> Result<PathResults, LicensureError> {
+        if is_archive(file) {
+            return self.license_archive(file).await;
+        }
+
+        if notebook::is_notebook(file) {
+            return Ok(vec![(file.to_string(), self.license_notebook(file).await?)]);
+        }
+
+        Ok(vec![(file.to_string(), self.license_file(file).await?)])
+    }
+
+    async fn license_file(&self, file: &str) -> Result<FileResult, LicensureError> {
+        if let Some(excluded) = self.excluded(file, file) {
+            return Ok(excluded);
+        }
+
+        let commented_as = self.commented_as(file);
+        let stamp = self.cache_file.as_ref().and_then(|_| Stamp::of(file));
+        let (result, licensed) = self
+            .license_content(file, &commented_as, file, stamp, || {
+                fs::read_to_string(file)
+            })
+            .await?;
+        if let Some(licensed) = licensed {
+            if self.changes_files() {
+                self.backup(file)?;
+                fs::write(file, licensed)?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// License the source of the first code or markdown cell of the
+    /// notebook file, commented like a file in the language of the
+    /// notebook for code cells. The rest of the notebook, such as its
+    /// metadata and the outputs of cells, is kept exactly as it is.
+    async fn license_notebook(&self, file: &str) -> Result<FileResult, LicensureError> {
+        if let Some(excluded) = self.excluded(file, file) {
+            return Ok(excluded);
+        }
+
+        let content = fs::read_to_string(file)?;
+        let cell: Cell = match notebook::first_cell(&content) {
+            Ok(Some(cell)) => cell,
+            Ok(None) => {
+                info!("skipping {} because it has no code or markdown cell.", file);
+                return Ok(FileResult::new(
+                    Outcome::Uncommentable,
+                    self.dry_run_note(file, "skipped, no code or markdown cell"),
+                ));
+            }
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is not a valid notebook: {}", file, e),
+                )
+                .into())
+            }
+        };
+
+        let commented_as = format!("{}{}", file, cell.extension);
+        let stamp = self.cache_file.as_ref().and_then(|_| Stamp::of(file));
+        let (mut result, licensed) = self
+            .license_content(file, &commented_as, file, stamp, || Ok(cell.source.clone()))
+            .await?;
+        if let Some(source) = licensed {
+            let licensed = notebook::with_source(&content, &cell, &source);
+            // Output is of the whole notebook, only a dry run shows the
+            // header the cell gets.
+            if !self.dry_run || self.patch_file.is_some() {
+                result.output = self.change_output(file, &content, &licensed, "", "");
+            }
+            if self.changes_files() {
+                self.backup(file)?;
+                fs::write(file, licensed)?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// License the text entries of the archive file, repackaging it
+    /// when changing files in place. Binary entries, directories, and
+    /// links are copied through untouched.
+    #[cfg(feature = "archives")]
+    async fn license_archive(&self, file: &str) -> Result<PathResults, LicensureError> {
+        let mut archive = Archive::open(file)?;
+        let mut results = Vec::new();
+        let mut changed = false;
+        for entry in archive.entries.iter_mut() {
+            if !entry.is_file() || entry.data.contains(&0) {
                 continue;
             }
-            files_not_licensed.push(file);
 
-            // if already licensed but the trailing lines/whitespace do not match
-            let content_trimmed = content.trim_end_matches(|c| c == '\n' || c == '\r' || c == ' ');
-            let header_trimmed = header.trim_end_matches(|c| c == '\n' || c == '\r' || c == ' ');
-            if content_trimmed.contains(header_trimmed) {
+            let content = match String::from_utf8(entry.data.clone()) {
+                Ok(content) => content,
+                Err(_) => continue,
+            };
+
+            let name = format!("{}:{}", file, entry.name);
+            if let Some(excluded) = self.excluded(&entry.name, &name) {
+                results.push((name, excluded));
+                continue;
+            }
+
+            let commented_as = self
+                .config
+                .comments
+                .commented_as(&entry.name, || content.lines().next().map(str::to_string));
+            let (mut result, licensed) = self
+                .license_content(&entry.name, &commented_as, &name, None, || Ok(content))
+                .await?;
+            if self.patch_file.is_some() {
+                result.output = None;
+            }
+            if let Some(licensed) = licensed {
+                entry.data = licensed.into_bytes();
+                changed = true;
+            }
+
+            results.push((name, result));
+        }
+
+        if changed && self.changes_files() {
+            self.backup(file)?;
+            archive.write(file)?;
+        }
+
+        Ok(results)
+    }
+
+    #[cfg(not(feature = "archives"))]
+    async fn license_archive(&self, _file: &str) -> Result<PathResults, LicensureError> {
+        Err(io::Error::other(
+            "Licensure is not compiled with 'archives' feature, so it cannot license archives",
+        )
+        .into())
+    }
+
+    /// License content as the content of a file named name, which only
+    /// chooses its license and commenter, without reading or writing
+    /// any file. Excludes do not apply as there is no such file.
+    /// Returns the report along with the licensed content, content
+    /// itself when it needs no change.
+    pub async fn license_text(
+        &self,
+        name: &str,
+        content: &str,
+    ) -> Result<(Report, String), LicensureError> {
+        let commented_as = self
+            .config
+            .comments
+            .commented_as(name, || content.lines().next().map(str::to_string));
+        let (result, licensed) = self
+            .license_content(name, &commented_as, name, None, || Ok(content.to_string()))
+            .await?;
+        let mut report = Report::default();
+        report.record(name, result.outcome);
+        Ok((report, licensed.unwrap_or_else(|| content.to_string())))
+    }
+
+    /// The result of skipping the file at path when it is excluded, or
+    /// is a backup or the cache, None when it should be licensed.
+    fn excluded(&self, path: &str, file: &str) -> Option<FileResult> {
+        if self.config.is_selected(path) && !self.is_backup(path) && !self.is_cache(path) {
+            return None;
+        }
+
+        Some(FileResult::new(
+            Outcome::Excluded,
+            self.dry_run_note(file, "skipped, excluded"),
+        ))
+    }
+
+    /// Determine the header for the file at path and license its
+    /// content, which is only read once the file is known not to be
+    /// skipped. Returns the outcome along with the licensed content
+    /// when it changes. The content is commented like the file
+    /// commented_as, path itself except for parts of a file such as a
+    /// notebook cell. file is the name of the file used in output, and
+    /// stamp is given when file can be cached.
+    async fn license_content<F>(
+        &self,
+        path: &str,
+        commented_as: &str,
+        file: &str,
+        stamp: Option<Stamp>,
+        read: F,
+    ) -> Result<(FileResult, Option<String>), LicensureError>
+    where
+        F: FnOnce() -> io::Result<String>,
+    {
+        let templ = match self.config.get_template(path).await? {
+            Some(t) => t,
+            None => {
+                info!("skipping {} because no license config matched.", file);
+                return Ok((
+                    FileResult::new(
+                        Outcome::Unmatched,
+                        self.dry_run_note(file, "skipped, no license config matched"),
+                    ),
+                    None,
+                ));
+            }
+        };
+
+        let (cfg, commenter) = self.config.comments.get_commenter(commented_as);
+        if cfg.is_uncommentable() {
+            info!(
+                "skipping {} because its format does not support comments.",
+                file
+            );
+            return Ok((
+                FileResult::new(
+                    Outcome::Uncommentable,
+                    self.dry_run_note(file, "skipped, format does not support comments"),
+                ),
+                None,
+            ));
+        }
+
+        let columns = templ.columns(cfg.get_columns());
+        let mut header = comment_header(&templ, commenter.as_ref(), columns)?;
+        trace!(
+            "{} is commented with {:?} and columns {:?}, expecting the header:\n{}",
+            file,
+            cfg.get_commenter(),
+            columns,
+            header
+        );
+        let cache_entry = stamp.map(|stamp| Entry::new(stamp, &header));
+        if let Some(entry) = cache_entry.filter(|entry| self.cache.is_fresh(file, entry)) {
+            info!("{} already licensed, unchanged since the last run", file);
+            return Ok((
+                FileResult::new(
+                    Outcome::AlreadyLicensed,
+                    self.dry_run_note(file, "skipped, already licensed"),
+                )
+                .set_cache_entry(Some(entry)),
+                None,
+            ));
+        }
+
+        let content = read()?;
+        if has_opt_out_marker(
+            &content,
+            &self.config.opt_out_marker,
+            self.config.copyright_search_lines,
+        ) {
+            info!("skipping {} because it opted out of licensing.", file);
+            return Ok((
+                FileResult::new(
+                    Outcome::OptedOut,
+                    self.dry_run_note(file, "skipped, opted out"),
+                ),
+                None,
+            ));
+        }
+
+        if frontmatter::is_markdown(commented_as) {
+            if let Some(range) = frontmatter::find(&content) {
+                return self.license_frontmatter(file, &templ.render()?, &content, range);
+            }
+        }
+
+        let line_ending = self.config.line_ending.for_content(&content);
+        if line_ending != "\n" {
+            header = header.replace('\n', line_ending);
+        }
+
+        let blank_lines = cfg.get_trailing_newlines();
+        if let Some(n) = blank_lines {
+            header = format!(
+                "{}{}",
+                header.trim_end_matches(['\n', '\r', ' ']),
+                line_ending.repeat(n + 1)
+            );
+        }
+
+        // Headers match the outdated pattern whatever their year or
+        // authors so a file licensed twice matches it more than once.
+        let outdated_re =
+            self.outdated_pattern(&templ.outdated_license_source(commenter.as_ref(), columns)?);
+        let headers: Vec<Range<usize>> =
+            outdated_re.find_iter(&content).map(|m| m.range()).collect();
+        trace!(
+            "{} matches the outdated header pattern {} times at {:?}, the pattern is {}",
+            file,
+            headers.len(),
+            headers,
+            outdated_re.as_str()
+        );
+        if headers.len() > 1 && !self.add_only && !self.normalize {
+            if !self.dedupe {
+                warn!("{} has {} license headers", file, headers.len());
+                return Ok((
+                    FileResult::new(
+                        Outcome::Duplicated,
+                        self.dry_run_note(file, "skipped, more than one license header"),
+                    ),
+                    None,
+                ));
+            }
+
+            info!("removing the duplicate license headers of {}", file);
+            let deduped = remove_headers(&content, &headers[1..]);
+            let output =
+                self.change_output(file, &content, &deduped, "would be deduplicated", &header);
+            return Ok((
+                FileResult::new(Outcome::Deduplicated, output),
+                Some(deduped),
+            ));
+        }
+
+        // A file which is nothing but its header may lack the final line
+        // ending of the header, like any file may lack its final newline.
+        let is_bare_header = !content.ends_with('\n')
+            && header
+                .strip_suffix(line_ending)
+                .is_some_and(|h| content.ends_with(h));
+        if content.contains(&header) || is_bare_header {
+            info!("{} already licensed", file);
+            return Ok((
+                FileResult::new(
+                    Outcome::AlreadyLicensed,
+                    self.dry_run_note(file, "skipped, already licensed"),
+                )
+                .set_cache_entry(cache_entry),
+                None,
+            ));
+        }
+
+        // if already licensed but the trailing lines/whitespace do not match
+        let content_trimmed = content.trim_end_matches(['\n', '\r', ' ']);
+        let header_trimmed = header.trim_end_matches(['\n', '\r', ' ']);
+        if self.normalize {
+            return self.reflow(file, &templ, commenter.as_ref(), &content, header_trimmed);
+        }
+
+        if self.add_only
+            && (!headers.is_empty()
+                || content_trimmed.contains(header_trimmed)
+                || has_copyright_notice(&content, self.config.copyright_search_lines))
+        {
+            info!("{} already has a license header, leaving it as is", file);
+            return Ok((
+                FileResult::new(
+                    Outcome::Kept,
+                    self.dry_run_note(file, "skipped, already has a license header"),
+                ),
+                None,
+            ));
+        }
+
+        if content_trimmed.contains(header_trimmed) {
+            info!(
+                "{} already licensed but the trailing lines/whitespace do not match",
+                file
+            );
+            // ignore the trailing lines for now so it does not result in duplicate license headers
+            return Ok((
+                FileResult::new(
+                    Outcome::TrailingMismatch,
+                    self.dry_run_note(
+                        file,
+                        "skipped, already licensed but the trailing lines/whitespace do not match",
+                    ),
+                ),
+                None,
+            )); // TODO fix the trailing whitespace or empty lines to match the template
+        }
+
+        if let Some(m) = outdated_re.find(&content) {
+            if !self.update {
                 info!(
-                    "{} already licensed but the trailing lines/whitespace do not match",
+                    "{} already licensed but the year or authors are outdated",
                     file
                 );
-                // ignore the trailing lines for now so it does not result in duplicate license headers
-                continue; // TODO fix the trailing whitespace or empty lines to match the template
+                return Ok((
+                    FileResult::new(
+                        Outcome::Outdated,
+                        self.dry_run_note(file, "skipped, the license header is outdated"),
+                    ),
+                    None,
+                ));
             }
 
-            header.push_str(&content);
+            info!("{} has an outdated license header, updating it", file);
+            // The match takes in up to as many line endings as the
+            // trailing lines of the header, replacing it with the header
+            // tops them up and keeps any more blank lines the file has.
+            let rest = &content[m.end()..];
+            let updated = match blank_lines {
+                Some(_) => format!(
+                    "{}{}{}",
+                    &content[..m.start()],
+                    header,
+                    strip_blank_lines(rest)
+                ),
+                // A file which is nothing but its header keeps lacking
+                // the final line ending.
+                None if rest.is_empty() && !m.as_str().ends_with('\n') => {
+                    format!("{}{}", &content[..m.start()], header_trimmed)
+                }
+                None => format!("{}{}{}", &content[..m.start()], header, rest),
+            };
+            let output = self.change_output(file, &content, &updated, "would be updated", &header);
+            return Ok((FileResult::new(Outcome::Updated, output), Some(updated)));
+        }
+
+        if templ.is_reuse() && has_spdx_tag(&content, self.config.copyright_search_lines) {
+            info!("{} already has a different REUSE header, skipping", file);
+            return Ok((
+                FileResult::new(
+                    Outcome::Foreign,
+                    self.dry_run_note(file, "skipped, foreign REUSE header"),
+                ),
+                None,
+            ));
+        }
+
+        if self.config.skip_if_any_copyright
+            && has_copyright_notice(&content, self.config.copyright_search_lines)
+        {
+            info!("{} already has a different license header, skipping", file);
+            return Ok((
+                FileResult::new(
+                    Outcome::Foreign,
+                    self.dry_run_note(file, "skipped, foreign license"),
+                ),
+                None,
+            ));
+        }
+
+        // A byte order mark must stay at the very start of the file so
+        // the header goes after it, as well as after any shebang or
+        // document declarations and modelines, or the line
+        // insert_after_pattern matches when configured.
+        let (bom, body) = match content.strip_prefix(UTF8_BOM) {
+            Some(body) => (UTF8_BOM, body),
+            None => ("", content.as_str()),
+        };
+        let (prolog, mut body) = match cfg.insert_position(body) {
+            Some(position) => body.split_at(position),
+            None => split_prolog(body, &self.config.modeline_patterns),
+        };
+        if blank_lines.is_some() {
+            body = strip_blank_lines(body);
+        }
+        let separator = if prolog.is_empty() || prolog.ends_with('\n') {
+            ""
+        } else {
+            line_ending
+        };
+        let licensed = format!("{}{}{}{}{}", bom, prolog, separator, header, body);
+        let output = self.change_output(file, &content, &licensed, "would be licensed", &header);
+        Ok((FileResult::new(Outcome::Licensed, output), Some(licensed)))
+    }
+
+    /// Rewrite the header of content to header when it only differs from
+    /// it in its wrapping or spacing, leaving content as is otherwise.
+    fn reflow(
+        &self,
+        file: &str,
+        templ: &Template,
+        commenter: &dyn Comment,
+        content: &str,
+        header: &str,
+    ) -> Result<(FileResult, Option<String>), LicensureError> {
+        let reflow_re = self.outdated_pattern(&templ.reflow_license_source(commenter)?);
+        // A header with the expected text which still matched is only
+        // followed by other trailing lines, which reflowing leaves.
+        let m = match reflow_re.find(content).filter(|m| m.as_str() != header) {
+            Some(m) => m,
+            None => {
+                info!("{} has no license header to reflow, leaving it as is", file);
+                return Ok((
+                    FileResult::new(
+                        Outcome::Kept,
+                        self.dry_run_note(file, "skipped, no license header to reflow"),
+                    ),
+                    None,
+                ));
+            }
+        };
+
+        info!(
+            "{} has a license header formatted differently, reflowing it",
+            file
+        );
+        let reflowed = format!("{}{}{}", &content[..m.start()], header, &content[m.end()..]);
+        let output = self.change_output(file, content, &reflowed, "would be reflowed", header);
+        Ok((FileResult::new(Outcome::Reflowed, output), Some(reflowed)))
+    }
 
-            if self.config.change_in_place {
-                let mut f = File::create(file)?;
-                f.write_all(header.as_bytes())?;
-            } else {
-                println!("{}", header);
+    /// License a Markdown file through a copyright entry in its YAML
+    /// frontmatter, found at range of content, instead of a comment.
+    fn license_frontmatter(
+        &self,
+        file: &str,
+        header: &str,
+        content: &str,
+        range: Range<usize>,
+    ) -> Result<(FileResult, Option<String>), LicensureError> {
+        let line_ending = self.config.line_ending.for_content(content);
+        let entry = frontmatter::copyright_entry(header, line_ending);
+        let (position, outcome) = match frontmatter::find_copyright(&content[range.clone()]) {
+            Some(existing) if content[range.clone()][existing.clone()] == entry => {
+                info!("{} already licensed", file);
+                return Ok((
+                    FileResult::new(
+                        Outcome::AlreadyLicensed,
+                        self.dry_run_note(file, "skipped, already licensed"),
+                    ),
+                    None,
+                ));
+            }
+            Some(_) if self.add_only => {
+                info!("{} already has a copyright entry, leaving it as is", file);
+                return Ok((
+                    FileResult::new(
+                        Outcome::Kept,
+                        self.dry_run_note(file, "skipped, already has a license header"),
+                    ),
+                    None,
+                ));
+            }
+            Some(_) if !self.update => {
+                info!(
+                    "{} already licensed but the copyright entry is outdated",
+                    file
+                );
+                return Ok((
+                    FileResult::new(
+                        Outcome::Outdated,
+                        self.dry_run_note(file, "skipped, the license header is outdated"),
+                    ),
+                    None,
+                ));
             }
+            Some(existing) => (
+                range.start + existing.start..range.start + existing.end,
+                Outcome::Updated,
+            ),
+            None => (range.end..range.end, Outcome::Licensed),
+        };
+
+        let licensed = format!(
+            "{}{}{}",
+            &content[..position.start],
+            entry,
+            &content[position.end..]
+        );
+        let action = match outcome {
+            Outcome::Updated => "would be updated",
+            _ => "would be licensed",
+        };
+        let output = self.change_output(file, content, &licensed, action, &entry);
+        Ok((FileResult::new(outcome, output), Some(licensed)))
+    }
+
+    /// The commented header file would get, None when no license config
+    /// matches it or its format does not support comments.
+    pub async fn header(&self, file: &str) -> Result<Option<String>, LicensureError> {
+        let templ = match self.config.get_template(file).await? {
+            Some(t) => t,
+            None => return Ok(None),
+        };
+
+        let (cfg, commenter) = self.config.comments.get_commenter(&self.commented_as(file));
+        if cfg.is_uncommentable() {
+            return Ok(None);
         }
 
-        Ok(files_not_licensed)
+        let columns = templ.columns(cfg.get_columns());
+        comment_header(&templ, commenter.as_ref(), columns).map(Some)
+    }
+
+    /// Whether file is a backup written by a run with the same backup
+    /// suffix.
+    fn is_backup(&self, file: &str) -> bool {
+        self.backup_suffix
+            .as_ref()
+            .is_some_and(|suffix| file.ends_with(suffix.as_str()))
+    }
+
+    /// Whether file is the cache file, which is never licensed.
+    fn is_cache(&self, file: &str) -> bool {
+        self.cache_file
+            .as_ref()
+            .is_some_and(|cache| file.trim_start_matches("./") == cache.trim_start_matches("./"))
+    }
+
+    /// Copy file to its backup before it is changed in place, when a
+    /// backup suffix is set.
+    fn backup(&self, file: &str) -> io::Result<()> {
+        if let Some(suffix) = &self.backup_suffix {
+            fs::copy(file, format!("{}{}", file, suffix))?;
+        }
+
+        Ok(())
+    }
+
+    /// The regex for the outdated header pattern source, compiled the
+    /// first time a file has it rather than again for every file.
+    fn outdated_pattern(&self, source: &str) -> Regex {
+        let mut patterns = self.outdated_patterns.lock().unwrap();
+        if let Some(re) = patterns.get(source) {
+            return re.clone();
+        }
+
+        let re = Regex::new(source).expect("escaped license header should always be a valid regex");
+        patterns.insert(source.to_string(), re.clone());
+        re
+    }
+
+    /// Whether licensed files are written, rather than only reported.
+    fn changes_files(&self) -> bool {
+        self.config.change_in_place && !self.dry_run && self.patch_file.is_none()
+    }
+
+    /// Describe what would happen to file when doing a dry run.
+    fn dry_run_note(&self, file: &str, action: &str) -> Option<String> {
+        if self.dry_run && self.patch_file.is_none() {
+            Some(format!("{}: {}\n", file, action))
+        } else {
+            None
+        }
+    }
+
+    /// The text to print for a file whose content changes from old to
+    /// new, the diff or new content when not changing files in place.
+    /// During a dry run the action and header are described instead.
+    fn change_output(
+        &self,
+        file: &str,
+        old: &str,
+        new: &str,
+        action: &str,
+        header: &str,
+    ) -> Option<String> {
+        if self.patch_file.is_some() {
+            let path = git::repo_path(file);
+            return Some(format!(
+                "diff --git a/{0} b/{0}\n{1}",
+                path,
+                unified_diff(&path, old, new)
+            ));
+        }
+
+        if self.dry_run {
+            return Some(format!("{}: {} with:\n{}\n", file, action, header));
+        }
+
+        if self.print_diff {
+            Some(unified_diff(file, old, new))
+        } else if self.config.change_in_place {
+            None
+        } else {
+            Some(format!("{}\n", new))
+        }
+    }
+}
+
+/// Render templ and comment it with commenter, wrapped at columns.
+fn comment_header(
+    templ: &Template,
+    commenter: &dyn Comment,
+    columns: Option<usize>,
+) -> Result<String, LicensureError> {
+    let uncommented = templ.render()?;
+    Ok(commenter.comment(&uncommented, columns))
+}
+
+/// Whether file is an archive whose entries are licensed instead of the
+/// file itself.
+fn is_archive(file: &str) -> bool {
+    [".tar.gz", ".tgz", ".zip"]
+        .iter()
+        .any(|ext| file.ends_with(ext))
+}
+
+/// The first line of file, read without loading the rest of it. None
+/// when it cannot be read or is not text.
+fn first_line(file: &str) -> Option<String> {
+    let mut line = String::new();
+    io::BufReader::new(fs::File::open(file).ok()?)
+        .take(FIRST_LINE_LIMIT)
+        .read_line(&mut line)
+        .ok()?;
+    Some(line)
+}
+
+/// Whether any of the first lines of content is a copyright notice or
+/// an SPDX tag.
+fn has_copyright_notice(content: &str, lines: usize) -> bool {
+    content
+        .lines()
+        .take(lines)
+        .any(|line| line.contains("Copyright") || line.contains("SPDX-"))
+}
+
+/// Whether any of the first lines of content is an SPDX tag of a REUSE
+/// header.
+fn has_spdx_tag(content: &str, lines: usize) -> bool {
+    content.lines().take(lines).any(|line| {
+        line.contains("SPDX-License-Identifier:") || line.contains("SPDX-FileCopyrightText:")
+    })
+}
+
+/// Whether any of the first lines of content contains marker, in any
+/// comment style. An empty marker disables opting out.
+fn has_opt_out_marker(content: &str, marker: &str, lines: usize) -> bool {
+    !marker.is_empty()
+        && content
+            .lines()
+            .take(lines)
+            .any(|line| line.contains(marker))
+}
+
+/// Remove the blank lines at the start of content so that a header can
+/// be followed by exactly the configured number of them.
+fn strip_blank_lines(content: &str) -> &str {
+    content.trim_start_matches(['\n', '\r'])
+}
+
+/// Whether e only stops the file it was met on from being licensed,
+/// such as the file being unreadable or its header failing to render,
+/// rather than the whole run.
+fn is_file_error(e: &LicensureError) -> bool {
+    matches!(e, LicensureError::Io(_) | LicensureError::Template { .. })
+}
+
+/// Remove the header ranges from content along with the blank lines
+/// following each of them.
+fn remove_headers(content: &str, headers: &[Range<usize>]) -> String {
+    let mut kept = String::with_capacity(content.len());
+    let mut last = 0;
+    for header in headers {
+        kept.push_str(&content[last..header.start]);
+        last = content.len() - strip_blank_lines(&content[header.end..]).len();
+    }
+
+    kept.push_str(&content[last..]);
+    kept
+}
+
+/// Split content into the leading lines which must stay at the start
+/// of the file, a shebang, XML declaration, doctype or line matching
+/// one of modelines, and the rest.
+fn split_prolog<'a>(content: &'a str, modelines: &RegexList) -> (&'a str, &'a str) {
+    let mut end = 0;
+    for (i, line) in content.split_inclusive('\n').enumerate() {
+        let lower = line.trim_start().to_ascii_lowercase();
+        let is_prolog = (i == 0 && line.starts_with("#!"))
+            || lower.starts_with("<?xml")
+            || lower.starts_with("<!doctype")
+            || modelines.is_match(line);
+        if !is_prolog {
+            break;
+        }
+
+        end += line.len();
+    }
+
+    content.split_at(end)
+}
+
+fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+fn unified_diff(file: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&format!("a/{}", file), &format!("b/{}", file))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::testing::TempDir;
+
+    const CONFIG: &str = r##"
+change_in_place: true
+excludes: []
+licenses:
+  - files: any
+    ident: MIT
+    authors:
+      - name: Mathew Robinson
+    year: "2020"
+    template: |
+      Copyright [year] [name of author]
+      Use of this source code is governed by the [ident] license.
+comments:
+  - extension: txt
+    commenter:
+      type: line
+      comment_char: "#"
+      trailing_lines: 1
+"##;
+
+    fn licensure() -> Licensure {
+        Licensure::new(serde_yaml::from_str(CONFIG).unwrap())
+    }
+
+    /// Write content to a file unique to the calling test and license it.
+    fn license_content(name: &str, content: &[u8]) -> Vec<u8> {
+        let dir = TempDir::new(name);
+        let file = dir.write("main.py", content);
+        block_on(licensure().license_files(std::slice::from_ref(&file))).unwrap();
+        fs::read(&file).unwrap()
+    }
+
+    #[test]
+    fn test_license_lf_file() {
+        assert_eq!(
+            "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\nprint(1)\n".as_bytes(),
+            license_content("lf", b"print(1)\n")
+        );
+    }
+
+    #[test]
+    fn test_license_crlf_file() {
+        let licensed = license_content("crlf", b"print(1)\r\nprint(2)\r\n");
+        let licensed = String::from_utf8(licensed).unwrap();
+        assert_eq!(
+            "# Copyright 2020 Mathew Robinson\r\n# Use of this source code is governed by the MIT license.\r\nprint(1)\r\nprint(2)\r\n",
+            licensed
+        );
+        assert!(!licensed.replace("\r\n", "").contains('\n'));
+    }
+
+    #[test]
+    fn test_license_file_with_bom() {
+        let licensed = license_content("bom", b"\xef\xbb\xbfprint(1)\n");
+        assert_eq!(b"\xef\xbb\xbf", &licensed[..3]);
+        assert_eq!(
+            "\u{feff}# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\nprint(1)\n",
+            String::from_utf8(licensed).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_license_file_with_bom_mid_stream() {
+        let licensed = license_content("bom-mid", "print(1)\n\u{feff}\n".as_bytes());
+        assert!(licensed.starts_with(b"# Copyright"));
+    }
+
+    #[test]
+    fn test_license_files_report() {
+        let dir = TempDir::new("report");
+        let files = vec![dir.write("report.py", "print(1)\n")];
+
+        let report = block_on(licensure().license_files(&files)).unwrap();
+        assert_eq!(files, report.not_licensed);
+        assert_eq!(files, report.missing);
+        assert_eq!(0, report.licensed);
+        assert!(report.fails(FailOn::Missing));
+        assert!(!report.fails(FailOn::Outdated));
+
+        let report = block_on(licensure().license_files(&files)).unwrap();
+        assert!(report.not_licensed.is_empty());
+        assert_eq!(1, report.licensed);
+        assert!(!report.fails(FailOn::Any));
+    }
+
+    #[test]
+    fn test_split_prolog() {
+        let licensure = licensure();
+        let modelines = &licensure.config.modeline_patterns;
+        assert_eq!(("", "print(1)\n"), split_prolog("print(1)\n", modelines));
+        assert_eq!(
+            ("#!/usr/bin/env python\n", "print(1)\n"),
+            split_prolog("#!/usr/bin/env python\nprint(1)\n", modelines)
+        );
+        assert_eq!(
+            ("<?xml version=\"1.0\"?>\n<!DOCTYPE svg>\n", "<svg/>\n"),
+            split_prolog(
+                "<?xml version=\"1.0\"?>\n<!DOCTYPE svg>\n<svg/>\n",
+                modelines
+            )
+        );
+        assert_eq!(
+            ("<!doctype html>", ""),
+            split_prolog("<!doctype html>", modelines)
+        );
+        assert_eq!(
+            ("", "x\n#!not a shebang\n"),
+            split_prolog("x\n#!not a shebang\n", modelines)
+        );
+        assert_eq!(
+            ("# vim: set ft=python:\n", "print(1)\n"),
+            split_prolog("# vim: set ft=python:\nprint(1)\n", modelines)
+        );
+        assert_eq!(
+            (
+                "#!/usr/bin/env python\n# -*- coding: utf-8 -*-\n",
+                "print(1)\n# vim: set ft=python:\n"
+            ),
+            split_prolog(
+                "#!/usr/bin/env python\n# -*- coding: utf-8 -*-\nprint(1)\n# vim: set ft=python:\n",
+                modelines
+            )
+        );
+        assert_eq!(
+            ("", "# service: web\n"),
+            split_prolog("# service: web\n", modelines)
+        );
+    }
+
+    #[test]
+    fn test_license_file_after_modeline() {
+        let header = "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n";
+        let content = "# vim: set ft=python:\nprint(1)\n";
+        let (_, licensed) = block_on(licensure().license_text("main.py", content)).unwrap();
+        assert_eq!(
+            format!("# vim: set ft=python:\n{}print(1)\n", header),
+            licensed
+        );
+
+        let config = format!("modeline_patterns: [\"^;; \"]\n{}", CONFIG);
+        let licensure = Licensure::new(serde_yaml::from_str(&config).unwrap());
+        let (_, licensed) = block_on(licensure.license_text("main.py", content)).unwrap();
+        assert_eq!(format!("{}{}", header, content), licensed);
+    }
+
+    #[test]
+    fn test_license_file_after_shebang() {
+        let licensed = license_content("shebang", b"#!/usr/bin/env python\nprint(1)\n");
+        assert_eq!(
+            "#!/usr/bin/env python\n# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\nprint(1)\n",
+            String::from_utf8(licensed).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_has_copyright_notice() {
+        let content = "#!/bin/sh\n# Copyright 2001 Someone Else\necho hi\n";
+        assert!(has_copyright_notice(content, 10));
+        assert!(!has_copyright_notice(content, 1));
+        assert!(has_copyright_notice(
+            "// SPDX-License-Identifier: BSD-3-Clause\n",
+            10
+        ));
+        assert!(!has_copyright_notice("fn main() {}\n", 10));
+        assert!(has_spdx_tag(
+            "# SPDX-FileCopyrightText: 2019 Someone Else\n",
+            10
+        ));
+        assert!(!has_spdx_tag(content, 10));
+    }
+
+    #[test]
+    fn test_trailing_newlines() {
+        let config = CONFIG.replace("extension: txt", "extension: py").replace(
+            "trailing_lines: 1\n",
+            "trailing_lines: 1\n    trailing_newlines: 2\n",
+        );
+        let dir = TempDir::new("trailing-newlines");
+        let path = dir.join("main.py");
+        let files = vec![path.to_string_lossy().to_string()];
+        let expected = "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n\n\nprint(1)\n";
+
+        fs::write(&path, "\n\n\n\nprint(1)\n").unwrap();
+        let licensure = Licensure::new(serde_yaml::from_str(&config).unwrap());
+        block_on(licensure.license_files(&files)).unwrap();
+        assert_eq!(expected, fs::read_to_string(&path).unwrap());
+
+        fs::write(
+            &path,
+            expected
+                .replace("2020", "2019")
+                .replace("\n\n\nprint", "\nprint"),
+        )
+        .unwrap();
+        let licensure = Licensure::new(serde_yaml::from_str(&config).unwrap()).set_update(true);
+        block_on(licensure.license_files(&files)).unwrap();
+        assert_eq!(expected, fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn test_update_wrapped_authors() {
+        let config = CONFIG
+            .replace(
+                "      - name: Mathew Robinson\n",
+                "      - name: Alice Example\n      - name: Bob Example\n      - name: Carol Example\n",
+            )
+            .replace("  - extension: txt\n", "  - extension: txt\n    columns: 30\n");
+        let dir = TempDir::new("update-wrapped-authors");
+        let files = vec![dir.write("notes.txt", "notes\n")];
+        block_on(Licensure::new(serde_yaml::from_str(&config).unwrap()).license_files(&files))
+            .unwrap();
+        let licensed = fs::read_to_string(&files[0]).unwrap();
+        assert!(licensed.starts_with("# Copyright 2020 Alice\n# Example, Bob Example, Carol\n"));
+
+        let config = config.replace("\"2020\"", "\"2021\"");
+        let report = block_on(
+            Licensure::new(serde_yaml::from_str(&config).unwrap())
+                .set_update(true)
+                .license_files(&files),
+        )
+        .unwrap();
+        assert_eq!(files, report.updated);
+        assert!(report.missing.is_empty());
+        let updated = fs::read_to_string(&files[0]).unwrap();
+        assert_eq!(licensed.replace("2020", "2021"), updated);
+        assert_eq!(1, updated.matches("Copyright").count());
+    }
+
+    #[test]
+    fn test_report_summary() {
+        let report = Report {
+            newly_licensed: 42,
+            licensed: 900,
+            updated: vec!["a.py".to_string()],
+            outdated: vec!["a.py".to_string(), "b.py".to_string()],
+            unmatched: 12,
+            uncommentable: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            "Licensed: 42, Already licensed: 900, Updated: 1, Outdated: 1, Skipped (no license config): 12, Skipped (no comment syntax): 3.",
+            report.summary()
+        );
+        assert_eq!(
+            "Licensed: 0, Already licensed: 0.",
+            Report::default().summary()
+        );
+    }
+
+    #[cfg(feature = "archives")]
+    #[test]
+    fn test_license_archive() {
+        let dir = TempDir::new("archive");
+        let path = dir.join("archive.tar.gz");
+        let file = path.to_string_lossy().to_string();
+        {
+            let gz = flate2::write::GzEncoder::new(
+                fs::File::create(&path).unwrap(),
+                flate2::Compression::default(),
+            );
+            let mut builder = tar::Builder::new(gz);
+            for (name, data) in [
+                ("src/main.py", &b"print(1)\n"[..]),
+                ("data.bin", &[0, 1, 2][..]),
+            ]
+            .iter()
+            {
+                let mut header = tar::Header::new_gnu();
+                header.set_mode(0o644);
+                header.set_size(data.len() as u64);
+                builder.append_data(&mut header, name, *data).unwrap();
+            }
+            builder.into_inner().unwrap().finish().unwrap();
+        }
+
+        let files = vec![file.clone()];
+        let report = block_on(licensure().license_files(&files)).unwrap();
+        assert_eq!(vec![format!("{}:src/main.py", file)], report.not_licensed);
+
+        let archive = Archive::open(&file).unwrap();
+        assert_eq!(
+            "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\nprint(1)\n".as_bytes(),
+            &archive.entries[0].data[..]
+        );
+        assert_eq!(vec![0, 1, 2], archive.entries[1].data);
+    }
+
+    #[test]
+    fn test_backup() {
+        let dir = TempDir::new("backup");
+        let file = dir.write("main.py", "print(1)\n");
+        let backup = format!("{}.orig", file);
+        let files = vec![file.clone(), backup.clone()];
+
+        let backing_up = || licensure().set_backup_suffix(Some(".orig".to_string()));
+        let report = block_on(backing_up().license_files(&files)).unwrap();
+        assert_eq!(vec![file.clone()], report.not_licensed);
+        assert_eq!(1, report.excluded);
+        assert_eq!("print(1)\n", fs::read_to_string(&backup).unwrap());
+
+        // Files which are not changed get no backup.
+        fs::remove_file(&backup).unwrap();
+        block_on(backing_up().license_files(&files[..1])).unwrap();
+        assert!(!std::path::Path::new(&backup).exists());
+    }
+
+    #[test]
+    fn test_uncommentable() {
+        let dir = TempDir::new("uncommentable");
+        let files = vec![dir.write("data.json", "{}\n")];
+
+        let report = block_on(licensure().license_files(&files)).unwrap();
+        assert_eq!(1, report.uncommentable);
+        assert!(report.not_licensed.is_empty());
+        assert_eq!("{}\n", fs::read_to_string(&files[0]).unwrap());
+    }
+
+    #[test]
+    fn test_progress() {
+        let dir = TempDir::new("progress");
+        let files: Vec<String> = (0..5)
+            .map(|i| dir.write(&format!("main{}.py", i), "print(1)\n"))
+            .collect();
+
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+        let licensure = licensure()
+            .set_jobs(3)
+            .set_progress(move |_, done, total| recorder.lock().unwrap().push((done, total)));
+        block_on(licensure.license_files(&files)).unwrap();
+
+        let mut seen = seen.lock().unwrap().clone();
+        seen.sort();
+        assert_eq!(
+            (1..=5).map(|done| (done, Some(5))).collect::<Vec<_>>(),
+            seen
+        );
+
+        // The total of streamed files is only given once all are found.
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+        let streaming = Licensure::new(serde_yaml::from_str(CONFIG).unwrap())
+            .set_progress(move |_, done, total| recorder.lock().unwrap().push((done, total)));
+        block_on(streaming.license_stream(files.into_iter().filter(|_| true))).unwrap();
+        let seen = seen.lock().unwrap();
+        assert_eq!(5, seen.len());
+        assert!(seen
+            .iter()
+            .all(|(_, total)| matches!(total, None | Some(5))));
+    }
+
+    #[test]
+    fn test_opt_out_marker() {
+        assert!(has_opt_out_marker(
+            "<!-- licensure:disable -->\n",
+            "licensure:disable",
+            10
+        ));
+        assert!(has_opt_out_marker(
+            "x\n# licensure:disable\n",
+            "licensure:disable",
+            10
+        ));
+        assert!(!has_opt_out_marker(
+            "x\n// licensure:disable\n",
+            "licensure:disable",
+            1
+        ));
+        assert!(!has_opt_out_marker("x\n", "", 10));
+
+        let content = "// Code generated by protoc. licensure:disable\nprint(1)\n";
+        assert_eq!(
+            content.as_bytes(),
+            license_content("opt-out", content.as_bytes())
+        );
+    }
+
+    #[test]
+    fn test_markdown() {
+        let dir = TempDir::new("markdown");
+        let path = dir.join("docs.md");
+        let files = vec![path.to_string_lossy().to_string()];
+        let run =
+            |update: bool| block_on(licensure().set_update(update).license_files(&files)).unwrap();
+        let licensed = "---\ntitle: Docs\ncopyright: |\n  Copyright 2020 Mathew Robinson\n  Use of this source code is governed by the MIT license.\n---\n# Docs\n";
+
+        fs::write(&path, "---\ntitle: Docs\n---\n# Docs\n").unwrap();
+        assert_eq!(1, run(false).newly_licensed);
+        assert_eq!(licensed, fs::read_to_string(&path).unwrap());
+        assert_eq!(1, run(false).licensed);
+
+        fs::write(&path, licensed.replace("2020", "2019")).unwrap();
+        assert_eq!(1, run(true).updated.len());
+        assert_eq!(licensed, fs::read_to_string(&path).unwrap());
+
+        fs::write(&path, "# Docs\n").unwrap();
+        run(false);
+        assert_eq!(
+            "<!--\nCopyright 2020 Mathew Robinson\nUse of this source code is governed by the MIT license.\n-->\n# Docs\n",
+            fs::read_to_string(&path).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_headers() {
+        let dir = TempDir::new("duplicates");
+        let path = dir.join("main.py");
+        let files = vec![path.to_string_lossy().to_string()];
+        let run =
+            |dedupe: bool| block_on(licensure().set_dedupe(dedupe).license_files(&files)).unwrap();
+        let header = "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n\n";
+        let twice = format!("{}{}print(1)\n", header, header.replace("2020", "2019"));
+
+        fs::write(&path, &twice).unwrap();
+        let report = run(false);
+        assert_eq!(files, report.duplicated);
+        assert_eq!(files, report.not_licensed);
+        assert_eq!(twice, fs::read_to_string(&path).unwrap());
+
+        let report = run(true);
+        assert_eq!(files, report.deduplicated);
+        assert_eq!(
+            format!("{}print(1)\n", header),
+            fs::read_to_string(&path).unwrap()
+        );
+        assert_eq!(1, run(false).licensed);
+    }
+
+    #[test]
+    fn test_license_toml() {
+        let dir = TempDir::new("toml");
+        let path = dir.join("config.toml");
+        let files = vec![path.to_string_lossy().to_string()];
+        let header = "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n";
+        let cases = vec![
+            (
+                "[package]\nname = \"a\"\n",
+                format!("{}[package]\nname = \"a\"\n", header),
+            ),
+            (
+                "\n# Build settings\n[package]\n",
+                format!("\n# Build settings\n{}[package]\n", header),
+            ),
+            ("name = \"a\"\n", format!("{}name = \"a\"\n", header)),
+        ];
+        for (content, expected) in cases {
+            fs::write(&path, content).unwrap();
+            block_on(licensure().license_files(&files)).unwrap();
+            assert_eq!(expected, fs::read_to_string(&path).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir = TempDir::new("cache");
+        let path = dir.join("main.py");
+        let cache = dir.join("cache.json");
+        let files = vec![path.to_string_lossy().to_string()];
+        let cached = |config: &str| {
+            block_on(
+                Licensure::new(serde_yaml::from_str(config).unwrap())
+                    .set_cache_file(Some(cache.to_string_lossy().to_string()))
+                    .license_files(&files),
+            )
+            .unwrap()
+        };
+
+        let licensed = "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n\nprint(1)\n";
+        fs::write(&path, licensed).unwrap();
+        assert_eq!(1, cached(CONFIG).licensed);
+
+        // The same size and modification time make the file look
+        // unchanged, so it is not read again.
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        fs::write(&path, licensed.replace("Mathew", "Someon")).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(1, cached(CONFIG).licensed);
+
+        // A different header does not match the cached one.
+        assert_eq!(1, cached(&CONFIG.replace("2020", "2021")).outdated.len());
+
+        let cache_file = vec![cache.to_string_lossy().to_string()];
+        let report = block_on(
+            licensure()
+                .set_cache_file(Some(cache_file[0].clone()))
+                .license_files(&cache_file),
+        )
+        .unwrap();
+        assert_eq!(1, report.excluded);
+    }
+
+    #[test]
+    fn test_add_only() {
+        let dir = TempDir::new("add-only");
+        let outdated = dir.join("outdated.py");
+        let missing = dir.join("missing.py");
+        let files = vec![
+            outdated.to_string_lossy().to_string(),
+            missing.to_string_lossy().to_string(),
+        ];
+        let header = "# Copyright 2019 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n\nprint(1)\n";
+        fs::write(&outdated, header).unwrap();
+        fs::write(&missing, "print(1)\n").unwrap();
+
+        let report = block_on(
+            licensure()
+                .set_update(true)
+                .set_add_only(true)
+                .license_files(&files),
+        )
+        .unwrap();
+        assert_eq!(1, report.kept);
+        assert_eq!(1, report.newly_licensed);
+        assert!(report.updated.is_empty());
+        assert_eq!(header, fs::read_to_string(&outdated).unwrap());
+        assert!(fs::read_to_string(&missing)
+            .unwrap()
+            .starts_with("# Copyright"));
+    }
+
+    #[test]
+    fn test_header() {
+        let licensure = licensure();
+        assert_eq!(
+            Some("# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n".to_string()),
+            block_on(licensure.header("file.py")).unwrap()
+        );
+        assert_eq!(None, block_on(licensure.header("file.json")).unwrap());
+    }
+
+    #[test]
+    fn test_no_trailing_newline() {
+        let header = "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n";
+        assert_eq!(
+            format!("{}print(1)", header).into_bytes(),
+            license_content("no-trailing-newline", b"print(1)")
+        );
+        assert_eq!(
+            format!("{}print(1)\n", header).into_bytes(),
+            license_content("trailing-newline", b"print(1)\n")
+        );
+
+        let dir = TempDir::new("bare-header");
+        let bare = header.trim_end();
+        let files = vec![dir.write("main.py", bare)];
+        let report = block_on(licensure().license_files(&files)).unwrap();
+        assert_eq!(1, report.licensed);
+        assert_eq!(bare, fs::read_to_string(&files[0]).unwrap());
+    }
+
+    #[test]
+    fn test_license_stream() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        config.add_exclude("^stream-").unwrap();
+        let produced = std::sync::Arc::new(AtomicUsize::new(0));
+        let counter = produced.clone();
+        let files = (0..500).map(move |i| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            format!("stream-{}.py", i)
+        });
+
+        let ahead = std::sync::Arc::new(AtomicUsize::new(0));
+        let (recorder, max_ahead) = (produced.clone(), ahead.clone());
+        let licensure = Licensure::new(config)
+            .set_jobs(2)
+            .set_progress(move |_, done, _| {
+                let queued = recorder.load(Ordering::SeqCst) - done;
+                max_ahead.fetch_max(queued, Ordering::SeqCst);
+            });
+        let report = block_on(licensure.license_stream(files)).unwrap();
+
+        assert_eq!(500, report.excluded);
+        assert_eq!(500, produced.load(Ordering::SeqCst));
+        // The files queued for the workers, those being licensed, and
+        // the one the producer waits to queue.
+        assert!(ahead.load(Ordering::SeqCst) <= 2 * QUEUED_PER_JOB + 2 + 1);
+    }
+
+    #[test]
+    fn test_not_licensed_order() {
+        let dir = TempDir::new("not-licensed-order");
+        let files: Vec<String> = (0..50)
+            .map(|i| dir.write(&format!("notes{}.txt", i), format!("notes {}\n", i)))
+            .collect();
+
+        let report = block_on(licensure().set_jobs(4).license_files(&files)).unwrap();
+        assert_eq!(files, report.not_licensed);
+        assert_eq!(files, report.missing);
+    }
+
+    /// License the file name with content through licensure, returning
+    /// the text output for it along with the file's content after.
+    fn license_output(licensure: Licensure, name: &str, content: &str) -> (String, String) {
+        let dir = TempDir::new(name);
+        let file = dir.write(name, content);
+        let outputs = std::sync::Arc::new(Mutex::new(String::new()));
+        let recorder = outputs.clone();
+        let licensure =
+            licensure.set_output(move |output| recorder.lock().unwrap().push_str(output));
+        block_on(licensure.license_files(std::slice::from_ref(&file))).unwrap();
+        let output = outputs.lock().unwrap().clone();
+        (output, fs::read_to_string(&file).unwrap())
+    }
+
+    #[test]
+    fn test_print_diff() {
+        let (output, after) =
+            license_output(licensure().set_print_diff(true), "diff.txt", "notes\n");
+        assert!(output.contains("@@ -1 +1,4 @@\n"));
+        assert!(output.contains("\n+# Copyright 2020 Mathew Robinson\n"));
+        assert!(output.contains("\n+# Use of this source code is governed by the MIT license.\n"));
+        assert!(output.contains("\n notes\n"));
+        // The diff is printed and the file is still licensed in place.
+        assert!(after.starts_with("# Copyright 2020 Mathew Robinson\n"));
+    }
+
+    #[test]
+    fn test_dry_run() {
+        // The test config changes files in place, a dry run still must not.
+        let (output, after) =
+            license_output(licensure().set_dry_run(true), "dry-run.txt", "notes\n");
+        assert!(output
+            .contains("dry-run.txt: would be licensed with:\n# Copyright 2020 Mathew Robinson\n"));
+        assert_eq!("notes\n", after);
+
+        // Diffs are shown by the dry run without writing either.
+        let (output, after) = license_output(
+            licensure().set_dry_run(true).set_print_diff(true),
+            "dry-run.txt",
+            "notes\n",
+        );
+        assert!(output.contains("would be licensed"));
+        assert_eq!("notes\n", after);
+    }
+
+    #[test]
+    fn test_output() {
+        let dir = TempDir::new("output");
+        let files: Vec<String> = (0..3)
+            .map(|i| dir.write(&format!("notes{}.txt", i), format!("notes {}\n", i)))
+            .collect();
+
+        let outputs = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let recorder = outputs.clone();
+        let config = CONFIG.replace("change_in_place: true", "change_in_place: false");
+        let licensure = Licensure::new(serde_yaml::from_str(&config).unwrap())
+            .set_jobs(3)
+            .set_output(move |output| recorder.lock().unwrap().push(output.to_string()));
+        block_on(licensure.license_files(&files)).unwrap();
+
+        let outputs = outputs.lock().unwrap();
+        assert_eq!(3, outputs.len());
+        for (i, (file, output)) in files.iter().zip(outputs.iter()).enumerate() {
+            assert!(output.starts_with("# Copyright 2020 Mathew Robinson\n"));
+            assert!(output.ends_with(&format!("notes {}\n\n", i)));
+            assert_eq!(format!("notes {}\n", i), fs::read_to_string(file).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_license_text() {
+        let mut config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        config.add_exclude(".*").unwrap();
+        let licensure = Licensure::new(config);
+        let header = "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n";
+
+        let (report, licensed) =
+            block_on(licensure.license_text("stdin.py", "print(1)\n")).unwrap();
+        assert_eq!(format!("{}print(1)\n", header), licensed);
+        assert_eq!(1, report.newly_licensed);
+        assert!(report.fails(FailOn::Any));
+
+        let (report, unchanged) = block_on(licensure.license_text("stdin.py", &licensed)).unwrap();
+        assert_eq!(licensed, unchanged);
+        assert!(!report.fails(FailOn::Any));
+    }
+
+    #[test]
+    fn test_outdated_patterns_are_reused() {
+        let config: Config = serde_yaml::from_str(CONFIG).unwrap();
+        let licensure = Licensure::new(config);
+        for name in ["a.py", "b.py", "c.py", "main.rs"].iter() {
+            block_on(licensure.license_text(name, "x = 1\n")).unwrap();
+        }
+
+        assert_eq!(2, licensure.outdated_patterns.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_license_notebook() {
+        let dir = TempDir::new("notebook");
+        let path = dir.join("notebook.ipynb");
+        let file = path.to_string_lossy().to_string();
+        let notebook = "{\n \"cells\": [\n  {\n   \"cell_type\": \"code\",\n   \"metadata\": {},\n   \"outputs\": [{\"output_type\": \"stream\", \"text\": [\"1\\n\"]}],\n   \"source\": [\n    \"print(1)\"\n   ]\n  }\n ],\n \"metadata\": {},\n \"nbformat\": 4\n}\n";
+        fs::write(&path, notebook).unwrap();
+
+        let files = vec![file.clone()];
+        let report = block_on(licensure().license_files(&files)).unwrap();
+        assert_eq!(1, report.newly_licensed);
+        assert_eq!(
+            notebook.replace(
+                "    \"print(1)\"",
+                "    \"# Copyright 2020 Mathew Robinson\\n\",\n    \"# Use of this source code is governed by the MIT license.\\n\",\n    \"print(1)\""
+            ),
+            fs::read_to_string(&path).unwrap()
+        );
+
+        let report = block_on(licensure().license_files(&files)).unwrap();
+        assert_eq!(1, report.licensed);
+    }
+
+    #[test]
+    fn test_file_names_and_shebangs() {
+        const NO_COMMENTS: &str = concat!(
+            "change_in_place: true\n",
+            "licenses:\n  - files: any\n    ident: MIT\n    authors: []\n    template: Licensed\n",
+        );
+        let dir = TempDir::new("file-names");
+        let files: Vec<String> = [
+            ("ci/Jenkinsfile", "pipeline {}\n"),
+            ("build/Makefile", "all:\n\ttrue\n"),
+            ("run", "#!/bin/sh\necho 1\n"),
+            ("serve", "#!/usr/bin/env node\nconsole.log(1)\n"),
+            ("notes", "text\n"),
+        ]
+        .iter()
+        .map(|(name, content)| dir.write(name, content))
+        .collect();
+
+        let report = block_on(
+            Licensure::new(serde_yaml::from_str(NO_COMMENTS).unwrap())
+                .set_strict(true)
+                .license_files(&files[..4]),
+        )
+        .unwrap();
+        assert_eq!(4, report.newly_licensed);
+        assert_eq!(
+            "// Licensed\npipeline {}\n",
+            fs::read_to_string(&files[0]).unwrap()
+        );
+        assert_eq!(
+            "# Licensed\nall:\n\ttrue\n",
+            fs::read_to_string(&files[1]).unwrap()
+        );
+        assert_eq!(
+            "#!/bin/sh\n# Licensed\necho 1\n",
+            fs::read_to_string(&files[2]).unwrap()
+        );
+        assert_eq!(
+            "#!/usr/bin/env node\n// Licensed\nconsole.log(1)\n",
+            fs::read_to_string(&files[3]).unwrap()
+        );
+
+        // Without a name or shebang telling its style a file is only
+        // licensed with the default one when not strict.
+        let strict = block_on(
+            Licensure::new(serde_yaml::from_str(NO_COMMENTS).unwrap())
+                .set_strict(true)
+                .license_files(&files[4..]),
+        );
+        assert!(matches!(strict, Err(LicensureError::UnknownExtension(_))));
+    }
+
+    #[test]
+    fn test_strict() {
+        let config: Config = serde_yaml::from_str(
+            "licenses:\n  - files: any\n    ident: MIT\n    authors: []\n    template: Licensed\n",
+        )
+        .unwrap();
+        let dir = TempDir::new("strict");
+        let files: Vec<String> = ["main.py", "notes.xyz", "data.abc"]
+            .iter()
+            .map(|name| dir.write(name, "text\n"))
+            .collect();
+
+        let err = block_on(
+            Licensure::new(config)
+                .set_strict(true)
+                .license_files(&files),
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(&err, LicensureError::UnknownExtension(files) if files.len() == 2));
+        assert!(!err.to_string().contains("main.py"));
+        assert!(err.to_string().contains("notes.xyz"));
+        assert!(err.to_string().contains("data.abc"));
+        assert_eq!("text\n", fs::read_to_string(&files[0]).unwrap());
+    }
+
+    #[test]
+    fn test_reuse_header() {
+        let config: Config = serde_yaml::from_str(
+            "licenses:\n  - files: any\n    ident: MIT\n    type: reuse\n    year: \"2020\"\n    authors:\n      - name: Mathew Robinson\n",
+        )
+        .unwrap();
+        let licensure = Licensure::new(config).set_update(true);
+        let header =
+            "# SPDX-FileCopyrightText: 2020 Mathew Robinson\n#\n# SPDX-License-Identifier: MIT\n";
+
+        let (_, licensed) = block_on(licensure.license_text("main.py", "print(1)\n")).unwrap();
+        assert_eq!(format!("{}print(1)\n", header), licensed);
+
+        let outdated =
+            "# SPDX-FileCopyrightText: 2018 Someone\n#\n# SPDX-License-Identifier: MIT\nprint(1)\n";
+        let (report, updated) = block_on(licensure.license_text("main.py", outdated)).unwrap();
+        assert_eq!(1, report.updated.len());
+        assert_eq!(format!("{}print(1)\n", header), updated);
+
+        let foreign = "# SPDX-FileCopyrightText: 2018 Someone\n# SPDX-License-Identifier: Apache-2.0\nprint(1)\n";
+        let (report, unchanged) = block_on(licensure.license_text("main.py", foreign)).unwrap();
+        assert_eq!(1, report.foreign.len());
+        assert_eq!(foreign, unchanged);
+    }
+
+    #[test]
+    fn test_trailing_lines_per_extension() {
+        let config = CONFIG.replace(
+            "  - extension: txt\n",
+            concat!(
+                "  - extension: py\n",
+                "    commenter:\n",
+                "      type: line\n",
+                "      comment_char: \"#\"\n",
+                "      trailing_lines: 1\n",
+                "  - extension: c\n",
+                "    commenter:\n",
+                "      type: block\n",
+                "      start_block_char: \"/*\\n\"\n",
+                "      end_block_char: \" */\\n\"\n",
+                "      per_line_char: \" *\"\n",
+                "      trailing_lines: 2\n",
+                "  - extension: txt\n",
+            ),
+        );
+        let licensure = Licensure::new(serde_yaml::from_str(&config).unwrap()).set_update(true);
+        let py = "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n";
+        let c = "/*\n * Copyright 2020 Mathew Robinson\n * Use of this source code is governed by the MIT license.\n */\n";
+
+        for (name, header, trailing_lines) in [("main.py", py, 1), ("main.c", c, 2)] {
+            let (_, licensed) = block_on(licensure.license_text(name, "x\n")).unwrap();
+            assert_eq!(
+                format!("{}{}x\n", header, "\n".repeat(trailing_lines)),
+                licensed
+            );
+
+            // Updating tops the blank lines up to the trailing lines and
+            // keeps any more the file has.
+            for blank_lines in [0, 1, 4] {
+                let outdated = format!(
+                    "{}{}x\n",
+                    header.replace("2020", "2018"),
+                    "\n".repeat(blank_lines)
+                );
+                let (report, updated) = block_on(licensure.license_text(name, &outdated)).unwrap();
+                assert_eq!(1, report.updated.len());
+                assert_eq!(
+                    format!(
+                        "{}{}x\n",
+                        header,
+                        "\n".repeat(blank_lines.max(trailing_lines))
+                    ),
+                    updated
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_unreadable_files() {
+        let dir = TempDir::new("unreadable-files");
+        fs::create_dir_all(dir.join("src.py")).unwrap();
+        let licensed = dir.join("main.py");
+        fs::write(&licensed, "print(1)\n").unwrap();
+        let files: Vec<String> = [dir.join("src.py"), dir.join("missing.py"), licensed.clone()]
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect();
+
+        let report = block_on(licensure().license_files(&files)).unwrap();
+        let failed: Vec<&str> = report.errors.iter().map(|e| e.file.as_str()).collect();
+        assert_eq!(vec![files[0].as_str(), files[1].as_str()], failed);
+        assert_eq!(vec![files[2].clone()], report.missing);
+        assert!(fs::read_to_string(&licensed)
+            .unwrap()
+            .starts_with("# Copyright 2020 Mathew Robinson"));
+        assert!(report.summary().contains("Failed: 2"));
+    }
+
+    #[test]
+    fn test_header_render_failure() {
+        // The Rust license uses [project] which is not configured.
+        let config = CONFIG.replace(
+            "licenses:\n",
+            "licenses:\n  - files: .*\\.rs$\n    ident: MIT\n    authors: []\n    template: \"[project]\"\n",
+        );
+        let dir = TempDir::new("header-render-failure");
+        let files = vec![
+            dir.write("main.rs", "fn main() {}\n"),
+            dir.write("notes.txt", "notes\n"),
+        ];
+
+        let report =
+            block_on(Licensure::new(serde_yaml::from_str(&config).unwrap()).license_files(&files))
+                .unwrap();
+        assert_eq!(1, report.errors.len());
+        assert_eq!(files[0], report.errors[0].file);
+        assert!(report.errors[0].error.contains("[project]"));
+        assert_eq!(vec![files[1].clone()], report.missing);
+    }
+
+    #[cfg(not(feature = "archives"))]
+    #[test]
+    fn test_archive_without_feature() {
+        let dir = TempDir::new("archive-without-feature");
+        let files = vec![dir.write("src.zip", "")];
+        let report = block_on(licensure().license_files(&files)).unwrap();
+        assert_eq!(files[0], report.errors[0].file);
+    }
+
+    #[test]
+    fn test_license_after_include_guard() {
+        let config = CONFIG.replace(
+            "  - extension: txt\n",
+            concat!(
+                "  - extensions: [h, hpp]\n",
+                "    insert_after_include_guard: true\n",
+                "    commenter:\n",
+                "      type: line\n",
+                "      comment_char: \"//\"\n",
+                "  - extension: txt\n",
+            ),
+        );
+        let licensure = Licensure::new(serde_yaml::from_str(&config).unwrap());
+        let header = "// Copyright 2020 Mathew Robinson\n// Use of this source code is governed by the MIT license.\n";
+
+        for guard in ["#pragma once\n", "#ifndef PARSER_H\n#define PARSER_H\n"] {
+            let content = format!("{}\nint parse();\n", guard);
+            let (_, licensed) = block_on(licensure.license_text("parser.h", &content)).unwrap();
+            assert_eq!(format!("{}{}\nint parse();\n", guard, header), licensed);
+
+            let (report, _) = block_on(licensure.license_text("parser.h", &licensed)).unwrap();
+            assert_eq!(1, report.licensed);
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let dir = TempDir::new("normalize");
+        let reflowed = dir.join("reflowed.py");
+        let outdated = dir.join("outdated.py");
+        let current = dir.join("current.py");
+        let files = vec![
+            reflowed.to_string_lossy().to_string(),
+            outdated.to_string_lossy().to_string(),
+            current.to_string_lossy().to_string(),
+        ];
+        let header = "# Copyright 2020 Mathew Robinson\n# Use of this source code is governed by the MIT license.\n\nprint(1)\n";
+        let misformatted = "# Copyright  2020 Mathew Robinson\n# Use of this   source code\n# is governed by the MIT license.\n\nprint(1)\n";
+        fs::write(&reflowed, misformatted).unwrap();
+        fs::write(&outdated, misformatted.replace("2020", "2019")).unwrap();
+        fs::write(&current, header).unwrap();
+
+        let report = block_on(licensure().set_normalize(true).license_files(&files)).unwrap();
+        assert_eq!(vec![files[0].clone()], report.reflowed);
+        assert_eq!(1, report.kept);
+        assert_eq!(1, report.licensed);
+        assert_eq!(header, fs::read_to_string(&reflowed).unwrap());
+        assert_eq!(
+            misformatted.replace("2020", "2019"),
+            fs::read_to_string(&outdated).unwrap()
+        );
+        assert_eq!(header, fs::read_to_string(&current).unwrap());
+    }
+
+    #[test]
+    fn test_patch_file() {
+        let dir = TempDir::new("patch");
+        let path = dir.join("main.py");
+        let patch = dir.join("licensing.diff");
+        let files = vec![path.to_string_lossy().to_string()];
+        fs::write(&path, "print(1)\n").unwrap();
+
+        let report = block_on(
+            licensure()
+                .set_patch_file(Some(patch.to_string_lossy().to_string()))
+                .license_files(&files),
+        )
+        .unwrap();
+        assert_eq!(1, report.newly_licensed);
+        assert_eq!("print(1)\n", fs::read_to_string(&path).unwrap());
+        let diff = fs::read_to_string(&patch).unwrap();
+        assert!(diff.starts_with("diff --git a/"), "{}", diff);
+        assert!(
+            diff.contains("\n+# Copyright 2020 Mathew Robinson\n"),
+            "{}",
+            diff
+        );
     }
 }
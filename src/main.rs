@@ -14,57 +14,79 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 extern crate chrono;
-#[macro_use]
-extern crate log;
-extern crate regex;
-extern crate serde;
-extern crate serde_yaml;
+extern crate licensure;
 
-mod comments;
-mod config;
-mod licensure;
-mod template;
+mod init;
+#[cfg(test)]
+#[path = "testing.rs"]
+mod testing;
 
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::ErrorKind;
 use std::path::Path;
 use std::process;
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use chrono::offset::{Offset, Utc};
 use clap::Arg;
+use ignore::WalkBuilder;
 
-use config::DEFAULT_CONFIG;
 use futures::executor::block_on;
-use licensure::Licensure;
+use licensure::config::{self, Config, InlineConfig, DEFAULT_CONFIG, DEFAULT_CONFIG_TOML};
+use licensure::{FailOn, Licensure, LicensureError, Report, CACHE_FILE};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
 const ABOUT: &str = env!("CARGO_PKG_DESCRIPTION");
 const HOMEPAGE: &str = env!("CARGO_PKG_HOMEPAGE");
+const GIT_COMMIT: &str = env!("LICENSURE_GIT_COMMIT");
+const BUILD_DATE: &str = env!("LICENSURE_BUILD_DATE");
+
+/// The mode git records for a submodule in the index.
+const GITLINK_MODE: &str = "160000";
 
 // FIXME: Possible that we should remove this functionality.
-fn get_project_files() -> Vec<String> {
-    let mut files = git_ls_files(Vec::new());
+fn get_project_files(pathspecs: &[&str]) -> Vec<String> {
+    // With --stage each tracked file is listed with its mode so that
+    // submodules can be left out.
+    let mut files: Vec<String> = git_ls_files(vec!["--stage"], pathspecs)
+        .iter()
+        .filter_map(|entry| staged_file(entry))
+        .map(str::to_string)
+        .collect();
 
-    let mut new_unstaged_files = git_ls_files(vec!["--others", "--exclude-standard"]);
+    let mut new_unstaged_files = git_ls_files(vec!["--others", "--exclude-standard"], pathspecs);
     files.append(&mut new_unstaged_files);
 
-    return files;
+    // git-ls still returns the removed files that are not committed, so we filter those out.
+    files.retain(|s| Path::new(s).exists());
+    files
+}
+
+/// Parse an entry of git ls-files --stage, returning its path unless
+/// it is a submodule.
+fn staged_file(entry: &str) -> Option<&str> {
+    let (info, path) = entry.split_once('\t')?;
+    if info.split(' ').next() == Some(GITLINK_MODE) {
+        return None;
+    }
+
+    Some(path)
 }
 
-fn git_ls_files(extra_args: Vec<&str>) -> Vec<String> {
+fn git_ls_files(extra_args: Vec<&str>, pathspecs: &[&str]) -> Vec<String> {
     match Command::new("git")
         .arg("ls-files")
         .args(extra_args)
+        .arg("--")
+        .args(pathspecs)
         .output()
     {
         Ok(proc) => String::from_utf8(proc.stdout)
             .unwrap()
             .split('\n')
-            // git-ls still returns the removed files that are not committed, so we filter those out.
-            .filter(|s| !s.is_empty() && Path::new(s).exists())
+            .filter(|s| !s.is_empty())
             .map(str::to_string)
             .collect(),
         Err(e) => {
@@ -75,62 +97,526 @@ fn git_ls_files(extra_args: Vec<&str>) -> Vec<String> {
     }
 }
 
+/// List the files changed between since and HEAD in the git repository
+/// which dir is in, as shown by git diff since...HEAD run from dir, so
+/// relative to it. Deleted and binary files are left out. An error
+/// telling why is returned when git fails, such as for an unknown ref.
+fn get_changed_files(dir: &Path, since: &str) -> Result<Vec<String>, String> {
+    let range = format!("{}...HEAD", since);
+    git_diff_files(dir, &[&range], &[])
+        .map_err(|e| format!("Failed to list the files changed since {}.\n{}", since, e))
+}
+
+/// List the files added, copied, or modified in the git staging area of
+/// the repository dir is in, relative to dir and limited to pathspecs
+/// when there are any. Deleted and binary files are left out.
+fn get_staged_files(dir: &Path, pathspecs: &[&str]) -> Result<Vec<String>, String> {
+    git_diff_files(dir, &["--cached", "--diff-filter=ACM"], pathspecs)
+        .map_err(|e| format!("Failed to list the staged files.\n{}", e))
+}
+
+/// The files of staged which also have changes in the working tree of
+/// dir which are not staged. Licensing them from the working tree would
+/// check, and with -i stage, content which is not part of the commit.
+fn get_partly_staged_files(dir: &Path, staged: &[String]) -> Result<Vec<String>, String> {
+    if staged.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let staged: Vec<&str> = staged.iter().map(String::as_str).collect();
+    git_diff_files(dir, &[], &staged)
+        .map_err(|e| format!("Failed to list the unstaged changes.\n{}", e))
+}
+
+/// Run git diff --name-only with args from dir, limited to pathspecs,
+/// listing the files it names which exist and are not binary.
+fn git_diff_files(dir: &Path, args: &[&str], pathspecs: &[&str]) -> Result<Vec<String>, String> {
+    match Command::new("git")
+        .args(["diff", "--name-only", "--relative"])
+        .args(args)
+        .arg("--")
+        .args(pathspecs)
+        .current_dir(dir)
+        .output()
+    {
+        Ok(proc) if proc.status.success() => Ok(String::from_utf8_lossy(&proc.stdout)
+            .lines()
+            .filter(|s| !s.is_empty() && dir.join(s).is_file() && !is_binary(&dir.join(s)))
+            .map(str::to_string)
+            .collect()),
+        Ok(proc) => Err(String::from_utf8_lossy(&proc.stderr).trim_end().to_string()),
+        Err(e) => Err(format!(
+            "Failed to run git diff. Make sure you're in a git repo.\n{}",
+            e
+        )),
+    }
+}
+
+/// Stage files again with git add, so that the headers just written
+/// are part of the commit being made.
+fn stage_files(files: &[&str]) {
+    if files.is_empty() {
+        return;
+    }
+
+    match Command::new("git")
+        .arg("add")
+        .arg("--")
+        .args(files)
+        .status()
+    {
+        Ok(status) if status.success() => (),
+        Ok(_) => {
+            println!("Failed to stage the licensed files.");
+            process::exit(1)
+        }
+        Err(e) => {
+            println!("Failed to run git add. Make sure you're in a git repo.");
+            println!("{}", e);
+            process::exit(1)
+        }
+    }
+}
+
+/// Recursively list the files under dir, honouring any .gitignore and
+/// .ignore files even when dir is not inside a git repository. Binary
+/// files are skipped. The files are found lazily as the walk goes so
+/// that they can be licensed before it completes.
+fn get_dir_files(dir: &str) -> impl Iterator<Item = String> + Send {
+    let dir = dir.to_string();
+    let walker = WalkBuilder::new(&dir)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+
+    walker.filter_map(move |entry| {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                println!("Failed to walk {}: {}", dir, e);
+                process::exit(1);
+            }
+        };
+
+        if !entry.file_type().is_some_and(|ft| ft.is_file()) || is_binary(entry.path()) {
+            return None;
+        }
+
+        let path = entry.path();
+        let path = path.strip_prefix("./").unwrap_or(path);
+        Some(path.to_string_lossy().to_string())
+    })
+}
+
+/// Sniff the first chunk of a file for NUL bytes which do not appear
+/// in text files.
+fn is_binary(path: &Path) -> bool {
+    let mut buf = [0; 8192];
+    match File::open(path).and_then(|mut f| f.read(&mut buf)) {
+        Ok(n) => buf[..n].contains(&0),
+        Err(_) => false,
+    }
+}
+
+/// Read a list of files from stdin separated by newlines, or by NUL
+/// bytes when nul_delimited is set.
+fn get_stdin_files(nul_delimited: bool) -> Vec<String> {
+    let mut input = String::new();
+    if let Err(e) = std::io::stdin().read_to_string(&mut input) {
+        println!("Failed to read file list from stdin: {}", e);
+        process::exit(1);
+    }
+
+    split_file_list(&input, nul_delimited)
+}
+
+fn split_file_list(input: &str, nul_delimited: bool) -> Vec<String> {
+    let sep = if nul_delimited { '\0' } else { '\n' };
+    input
+        .split(sep)
+        .map(|s| s.trim_end_matches('\r'))
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Build a config from the command line flags for use without a config
+/// file.
+fn inline_config(
+    template_file: &str,
+    matches: &clap::ArgMatches,
+) -> Result<Config, LicensureError> {
+    let template = std::fs::read_to_string(template_file).map_err(|e| LicensureError::Config {
+        paths: Vec::new(),
+        message: format!(
+            "unable to read the --template-file {}: {}",
+            template_file, e
+        ),
+    })?;
+
+    let columns = match matches.value_of("columns").map(str::parse) {
+        Some(Ok(n)) => Some(n),
+        Some(Err(e)) => {
+            println!("Invalid value for --columns: {}", e);
+            process::exit(1);
+        }
+        None => None,
+    };
+
+    Config::inline(InlineConfig {
+        template,
+        ident: matches.value_of("ident").unwrap_or_default().to_string(),
+        authors: matches
+            .values_of("author")
+            .map(|authors| authors.map(str::to_string).collect())
+            .unwrap_or_default(),
+        comment_prefix: matches.value_of("comment-prefix").map(str::to_string),
+        columns,
+    })
+}
+
+/// Check that ident is made of the characters SPDX license
+/// identifiers use, allowing license expressions such as
+/// "MIT OR Apache-2.0".
+fn looks_like_spdx_ident(ident: &str) -> bool {
+    let tokens: Vec<&str> = ident
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|t| !t.is_empty())
+        .collect();
+
+    // Identifiers and operators must alternate, starting and ending
+    // with an identifier.
+    tokens.len() % 2 == 1
+        && tokens.iter().enumerate().all(|(i, t)| {
+            let is_operator = matches!(*t, "AND" | "OR" | "WITH");
+            let is_ident = t
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'));
+            if i % 2 == 1 {
+                is_operator
+            } else {
+                is_ident && !is_operator
+            }
+        })
+}
+
 fn main() {
-    let matches = clap::Command::new("licensure")
-        .version(VERSION)
-        .author("Mathew Robinson <chasinglogic@gmail.com>")
-        .about(
-            format!(
-                "{}
+    let about = format!(
+        "{}
 
 {}
 
 More information is available at: {}",
-                ABOUT,
-                AUTHORS.replace(":", ", "),
-                HOMEPAGE
-            )
-            .as_str(),
-        )
+        ABOUT,
+        AUTHORS.replace(":", ", "),
+        HOMEPAGE
+    );
+    let app = clap::Command::new("licensure")
+        .version(VERSION)
+        .disable_version_flag(true)
+        .author("Mathew Robinson <chasinglogic@gmail.com>")
+        .about(about.as_str())
         .arg(
             Arg::new("verbose")
                 .short('v')
                 .long("verbose")
-                .multiple_occurrences(true),
+                .multiple_occurrences(true)
+                .help("Log what is done to each file, -vvv adds debug logs and -vvvv also logs the commenter, expected header, and outdated pattern matches of each file"),
+        )
+        .arg(
+            Arg::new("version")
+                .short('V')
+                .long("version")
+                .help("Print version information, with --verbose also the git commit and date it was built from"),
+        )
+        .arg(
+            Arg::new("in-place")
+                .short('i')
+                .long("in-place")
+                .help("Write the licensed content back to each file instead of printing it"),
+        )
+        .arg(
+            Arg::new("fix")
+                .long("fix")
+                .conflicts_with("check")
+                .help("Make every file correct, the same as --in-place --update, use --report-and-fix to also report like --check"),
+        )
+        .arg(
+            Arg::new("report-and-fix")
+                .long("report-and-fix")
+                .conflicts_with_all(&["check", "add-only", "ext", "print-header"])
+                .help("Print the files --check would report then fix them like --fix, exiting nonzero when any file needed fixing"),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("Do not print a summary of the results at the end of the run"),
+        )
+        .arg(
+            Arg::new("backup")
+                .long("backup")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .default_missing_value(".orig")
+                .value_name("SUFFIX")
+                .help("Before changing a file in place copy its original content to the file name followed by SUFFIX, defaults to .orig"),
+        )
+        .arg(Arg::new("dry-run").long("dry-run").help(
+            "Print what would be done to each file and the header it would get without changing any files",
+        ))
+        .arg(
+            Arg::new("update")
+                .long("update")
+                .help("Replace license headers which only differ by their year or authors with the current header"),
+        )
+        .arg(
+            Arg::new("print-header")
+                .long("print-header")
+                .takes_value(true)
+                .value_name("EXT")
+                .help("Print the commented header files with the extension EXT would get, without licensing any file"),
+        )
+        .arg(
+            Arg::new("add-only")
+                .long("add-only")
+                .conflicts_with_all(&["update", "fix", "dedupe"])
+                .help("Only add headers to files which have none, leaving any existing header as is even when it is outdated"),
+        )
+        .arg(
+            Arg::new("strict")
+                .long("strict")
+                .help("Fail without licensing or changing any file when a file has no known comment style, listing all such files, instead of commenting it with #"),
+        )
+        .arg(
+            Arg::new("cache")
+                .long("cache")
+                .help("Skip files which had the expected header on an earlier run with --cache and are unchanged since, recorded in .licensure-cache"),
+        )
+        .arg(
+            Arg::new("dedupe")
+                .long("dedupe")
+                .help("Remove all but the first license header of files which have more than one"),
         )
-        .arg(Arg::new("in-place").short('i').long("in-place"))
         .arg(
             Arg::new("check")
                 .long("check")
                 .help("Checks if any file is not licensed with the given config"),
         )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .requires("check")
+                .help("Print the results of --check as a JSON object"),
+        )
+        .arg(
+            Arg::new("fail-on")
+                .long("fail-on")
+                .takes_value(true)
+                .possible_values(["outdated", "missing", "any"])
+                .requires("check")
+                .help("Which files make --check fail, those with an outdated header, those missing a header, or any not licensed file, defaults to any"),
+        )
+        .arg(Arg::new("diff").long("diff").help(
+            "Print a unified diff of the changes that would be made instead of the licensed file contents",
+        ))
+        .arg(
+            Arg::new("patch")
+                .long("patch")
+                .takes_value(true)
+                .value_name("FILE")
+                .conflicts_with_all(&["diff", "dry-run", "in-place", "fix", "report-and-fix", "ext"])
+                .help("Write the changes that would be made to every file to FILE as one patch for git apply, without changing any file"),
+        )
         .arg(
             Arg::new("exclude")
                 .short('e')
                 .long("exclude")
                 .takes_value(true)
+                .multiple_occurrences(true)
                 .value_name("REGEX")
-                .help("A regex which will be used to determine what files to ignore."),
+                .help("A regex which will be used to determine what files to ignore, in addition to the excludes of the config. May be given more than once."),
+        )
+        .arg(
+            Arg::new("exclude-ext")
+                .long("exclude-ext")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .value_name("EXTS")
+                .help("A comma separated list of file extensions to ignore, in any case, such as lock,min.js. May be given more than once."),
+        )
+        .arg(
+            Arg::new("ident")
+                .long("ident")
+                .takes_value(true)
+                .value_name("SPDX_ID")
+                .help("Override the license identifier of every license in the config"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .takes_value(true)
+                .value_name("PATH")
+                .conflicts_with("template-file")
+                .help("Use the config file at PATH instead of searching for one"),
+        )
+        .arg(
+            Arg::new("template-file")
+                .long("template-file")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("License files with the template in PATH instead of using a config file"),
+        )
+        .arg(
+            Arg::new("author")
+                .long("author")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .value_name("AUTHOR")
+                .requires("template-file")
+                .help("An author, as \"Name\" or \"Name <email>\", for --template-file"),
+        )
+        .arg(
+            Arg::new("comment-prefix")
+                .long("comment-prefix")
+                .takes_value(true)
+                .value_name("PREFIX")
+                .requires("template-file")
+                .help("Comment every file with PREFIX when using --template-file"),
+        )
+        .arg(
+            Arg::new("columns")
+                .long("columns")
+                .takes_value(true)
+                .value_name("N")
+                .requires("comment-prefix")
+                .help("Wrap headers to N columns when using --comment-prefix"),
+        )
+        .arg(
+            Arg::new("jobs")
+                .short('j')
+                .long("jobs")
+                .takes_value(true)
+                .value_name("N")
+                .help("Number of files to process in parallel, defaults to the number of CPUs"),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .help("Show a progress bar on stderr while files are processed"),
         )
         .arg(Arg::new("project").long("project").short('p').help(
-            "When specified will license the current project files as returned by git ls-files",
+            "When specified will license the current project files as returned by git ls-files, skipping submodules",
         ))
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .takes_value(true)
+                .value_name("REF")
+                .help("License the files changed between the git REF and HEAD"),
+        )
+        .arg(
+            Arg::new("staged")
+                .long("staged")
+                .conflicts_with_all(&["project", "since", "dir", "stdin-files"])
+                .help("License the files added or modified in the git staging area, with --in-place the licensed files are staged again, for pre-commit hooks"),
+        )
+        .arg(
+            Arg::new("dir")
+                .long("dir")
+                .takes_value(true)
+                .value_name("PATH")
+                .help("License the files found by walking PATH, respecting .gitignore and .ignore files"),
+        )
+        .arg(
+            Arg::new("ext")
+                .long("ext")
+                .takes_value(true)
+                .value_name("EXT")
+                .conflicts_with_all(&["project", "since", "staged", "dir", "stdin-files", "in-place", "fix"])
+                .help("License the content read from stdin as a file with the extension EXT and print the result, with --check only exit nonzero when it is not licensed"),
+        )
+        .arg(
+            Arg::new("stdin-files")
+                .long("stdin-files")
+                .help("Read the files to license from stdin, one per line. Passing - as FILES does the same"),
+        )
+        .arg(
+            Arg::new("null")
+                .short('0')
+                .long("null")
+                .help("Files read from stdin are separated by NUL bytes instead of newlines"),
+        )
+        .subcommand(
+            clap::Command::new("init")
+                .about("Write a .licensure.yml for the license, author, and columns you give"),
+        )
         .arg(
             Arg::new("generate-config")
                 .long("generate-config")
                 .help("Generate a default licensure config file"),
         )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(["yaml", "toml"])
+                .requires("generate-config")
+                .help("The format of the config file written by --generate-config, defaults to yaml"),
+        )
         .arg(
             Arg::new("FILES")
                 .multiple_occurrences(true)
-                .help("Files to license, ignored if --project is supplied"),
+                .help("Files to license, with --project or --staged these are pathspecs limiting the project or staged files"),
+        );
+
+    // normalize takes the same options as licensing files does.
+    let args: Vec<Arg> = app
+        .get_arguments()
+        .filter(|arg| !["help", "version"].contains(&arg.get_id()))
+        .cloned()
+        .collect();
+    let matches = app
+        .subcommand(
+            clap::Command::new("normalize")
+                .about("Rewrite the license headers which only differ from the expected one in their wrapping or spacing, leaving the year and authors of the others as they are")
+                .args(args),
         )
         .get_matches();
 
+    if matches.is_present("version") {
+        println!("licensure {}", VERSION);
+        if matches.is_present("verbose") {
+            println!("commit: {}", GIT_COMMIT);
+            println!("build date: {}", BUILD_DATE);
+        }
+        process::exit(0);
+    }
+
+    if matches.subcommand_matches("init").is_some() {
+        if let Err(e) = init::run() {
+            println!("{}", e);
+            process::exit(1);
+        }
+
+        process::exit(0);
+    }
+
+    let (matches, normalize) = match matches.subcommand() {
+        Some(("normalize", normalize)) => (normalize.clone(), true),
+        _ => (matches, false),
+    };
+
     match matches.occurrences_of("verbose") {
         0 => (),
         x => simplelog::SimpleLogger::init(
-            if x > 2 {
+            if x > 3 {
+                simplelog::LevelFilter::Trace
+            } else if x > 2 {
                 simplelog::LevelFilter::Debug
             } else {
                 simplelog::LevelFilter::Info
@@ -147,66 +633,296 @@ More information is available at: {}",
     };
 
     if matches.is_present("generate-config") {
-        let mut f = match File::create(".licensure.yml") {
+        let (filename, default_config) = match matches.value_of("format") {
+            Some("toml") => (".licensure.toml", DEFAULT_CONFIG_TOML),
+            _ => (".licensure.yml", DEFAULT_CONFIG),
+        };
+
+        let mut f = match File::create(filename) {
             Ok(f) => f,
             Err(e) => {
-                println!("Unable to create .licensure.yml: {}", e);
+                println!("Unable to create {}: {}", filename, e);
                 process::exit(1);
             }
         };
 
-        if let Err(e) = f.write_all(DEFAULT_CONFIG.as_bytes()) {
-            println!("Unable to write to .licensure.yml: {}", e);
+        if let Err(e) = f.write_all(default_config.as_bytes()) {
+            println!("Unable to write to {}: {}", filename, e);
             process::exit(1);
         }
 
         process::exit(0);
     }
 
-    let files: Vec<String> = if matches.is_present("project") {
-        get_project_files()
-    } else {
-        matches
-            .values_of("FILES")
-            .expect("ERROR: Must provide files to license either as matches or via --project")
-            .map(str::to_string)
-            .collect()
+    let loaded = match (
+        matches.value_of("template-file"),
+        matches.value_of("config"),
+    ) {
+        (Some(path), _) => inline_config(path, &matches),
+        (None, Some(path)) => config::load_config_from(Path::new(path)),
+        (None, None) => config::load_config(),
     };
 
-    let mut config = match config::load_config() {
+    let mut config = match loaded {
         Ok(c) => c,
+        Err(LicensureError::ConfigNotFound) => {
+            println!("No config file found, generate one with licensure --generate-config");
+            process::exit(1);
+        }
         Err(e) => {
-            if ErrorKind::NotFound == e.kind() {
-                println!("No config file found, generate one with licensure --generate-config");
-            } else {
-                println!("Error loading config file: {}", e);
-            }
-
+            println!("Error loading config file: {}", e);
             process::exit(1);
         }
     };
 
-    if let Some(exclude) = matches.value_of("exclude") {
-        config.add_exclude(exclude);
+    for warning in config.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    for exclude in matches.values_of("exclude").into_iter().flatten() {
+        if let Err(e) = config.add_exclude(exclude) {
+            println!("Invalid --exclude: {}", e);
+            process::exit(1);
+        }
+    }
+
+    for ext in matches.values_of("exclude-ext").into_iter().flatten() {
+        if let Err(e) = config.add_exclude_ext(ext) {
+            println!("Invalid --exclude-ext: {}", e);
+            process::exit(1);
+        }
+    }
+
+    if let Some(ident) = matches.value_of("ident") {
+        let ident = ident.trim();
+        if ident.is_empty() {
+            println!("--ident must not be empty");
+            process::exit(1);
+        }
+
+        if !looks_like_spdx_ident(ident) {
+            eprintln!(
+                "Warning: {} does not look like an SPDX license identifier, see https://spdx.org/licenses/",
+                ident
+            );
+        }
+
+        config.set_ident(ident);
     }
 
-    if matches.is_present("in-place") {
+    let fix = matches.is_present("fix") || matches.is_present("report-and-fix");
+    if matches.is_present("in-place") || fix {
         config.change_in_place = true;
     }
+    let restage = matches.is_present("staged")
+        && config.change_in_place
+        && !matches.is_present("dry-run")
+        && !matches.is_present("patch");
+
+    if matches.value_of("backup") == Some("") {
+        println!("The --backup suffix must not be empty.");
+        process::exit(1);
+    }
+
+    let mut licensure = Licensure::new(config)
+        .set_print_diff(matches.is_present("diff"))
+        .set_patch_file(matches.value_of("patch").map(String::from))
+        .set_update(matches.is_present("update") || fix)
+        .set_dedupe(matches.is_present("dedupe"))
+        .set_add_only(matches.is_present("add-only"))
+        .set_normalize(normalize)
+        .set_strict(matches.is_present("strict"))
+        .set_dry_run(matches.is_present("dry-run"))
+        .set_backup_suffix(matches.value_of("backup").map(str::to_string))
+        .set_cache_file(matches.is_present("cache").then(|| CACHE_FILE.to_string()));
+    if let Some(jobs) = matches.value_of("jobs") {
+        match jobs.parse() {
+            Ok(n) => licensure = licensure.set_jobs(n),
+            Err(e) => {
+                println!("Invalid value for --jobs {}: {}", jobs, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if !matches.is_present("json") {
+        licensure = licensure.set_output(|output| print!("{}", output));
+    }
+
+    if matches.is_present("progress") {
+        licensure = licensure.set_progress(progress_bar());
+    }
+
+    let fail_on = match matches.value_of("fail-on") {
+        Some("outdated") => FailOn::Outdated,
+        Some("missing") => FailOn::Missing,
+        _ => FailOn::Any,
+    };
+
+    if let Some(ext) = matches.value_of("ext") {
+        let mut content = String::new();
+        if let Err(e) = std::io::stdin().read_to_string(&mut content) {
+            println!("Failed to read the content to license from stdin: {}", e);
+            process::exit(1);
+        }
+
+        let name = format!("stdin.{}", ext.trim_start_matches('.'));
+        match block_on(licensure.license_text(&name, &content)) {
+            Ok((report, licensed)) => {
+                if !matches.is_present("check") {
+                    print!("{}", licensed);
+                } else if report.fails(fail_on) {
+                    eprintln!("The content read from stdin is not licensed.");
+                    process::exit(1);
+                }
+            }
+            Err(e) => {
+                println!("Failed to license the content read from stdin: {}", e);
+                process::exit(1);
+            }
+        }
+
+        process::exit(0);
+    }
+
+    if let Some(ext) = matches.value_of("print-header") {
+        let ext = ext.trim_start_matches('.');
+        let file = format!("file.{}", ext);
+        match block_on(licensure.header(&file)) {
+            Ok(Some(header)) => print!("{}", header),
+            Ok(None) => {
+                println!("No header for .{} files, no license config matches them or their format does not support comments.", ext);
+                process::exit(1);
+            }
+            Err(e) => {
+                println!("Failed to render the header: {}", e);
+                process::exit(1);
+            }
+        }
+
+        process::exit(0);
+    }
+
+    let read_stdin = matches.is_present("stdin-files")
+        || matches
+            .values_of("FILES")
+            .is_some_and(|mut files| files.any(|f| f == "-"));
+
+    let pathspecs: Vec<&str> = matches
+        .values_of("FILES")
+        .map(Iterator::collect)
+        .unwrap_or_default();
+    let staged = if matches.is_present("staged") {
+        let found = get_staged_files(Path::new("."), &pathspecs).and_then(|staged| {
+            get_partly_staged_files(Path::new("."), &staged).map(|partly| (staged, partly))
+        });
+        match found {
+            Ok((_, partly)) if !partly.is_empty() => {
+                println!(
+                    "The following staged files also have unstaged changes, stage or stash them first so that what is checked is what gets committed:\n{}",
+                    partly.join("\n")
+                );
+                process::exit(1);
+            }
+            Ok((staged, _)) => staged,
+            Err(e) => {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+    } else {
+        Vec::new()
+    };
 
+    let files: Box<dyn Iterator<Item = String> + Send> = if matches.is_present("project") {
+        Box::new(get_project_files(&pathspecs).into_iter())
+    } else if matches.is_present("staged") {
+        Box::new(staged.clone().into_iter())
+    } else if let Some(since) = matches.value_of("since") {
+        match get_changed_files(Path::new("."), since) {
+            Ok(files) => Box::new(files.into_iter()),
+            Err(e) => {
+                println!("{}", e);
+                process::exit(1);
+            }
+        }
+    } else if let Some(dir) = matches.value_of("dir") {
+        Box::new(get_dir_files(dir))
+    } else if read_stdin {
+        Box::new(get_stdin_files(matches.is_present("null")).into_iter())
+    } else {
+        Box::new(
+            matches
+                .values_of("FILES")
+                .expect("ERROR: Must provide files to license either as matches or via --project")
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    };
+
+    let progress = matches.is_present("progress");
     let done = async {
-        match Licensure::new(config).license_files(&files).await {
+        let licensed = licensure.license_stream(files).await;
+        if progress {
+            eprintln!();
+        }
+
+        match licensed {
+            Err(LicensureError::UnknownExtension(files)) => {
+                println!(
+                    "No file was licensed as no comment style is known for the following files, add a comments config for them or exclude them:\n{}",
+                    files.join("\n")
+                );
+                process::exit(1);
+            }
             Err(e) => {
                 println!("Failed to license files: {}", e);
                 process::exit(1);
             }
-            Ok(files_not_licensed) => {
-                if matches.is_present("check") && !files_not_licensed.is_empty() {
-                    eprintln!("The following files were not licensed with the given config.");
-                    for file in files_not_licensed {
-                        eprintln!("{}", file);
+            Ok(report) => {
+                if restage {
+                    let changed: Vec<&str> = report
+                        .changed()
+                        .into_iter()
+                        .filter(|f| staged.iter().any(|s| s == f))
+                        .collect();
+                    stage_files(&changed);
+                }
+
+                if matches.is_present("json") {
+                    match serde_json::to_string_pretty(&report) {
+                        Ok(json) => println!("{}", json),
+                        Err(e) => {
+                            println!("Failed to serialize results: {}", e);
+                            process::exit(1);
+                        }
                     }
-                    process::exit(1);
+                } else {
+                    // Nothing was done when only describing or patching
+                    // the changes.
+                    let changed = !matches.is_present("dry-run") && !matches.is_present("patch");
+                    print_run_results(&report, changed, matches.is_present("dedupe"));
+
+                    // The report tells the state files were in before
+                    // this run, so it also lists the files just fixed.
+                    if matches.is_present("check") || matches.is_present("report-and-fix") {
+                        print_check_results(&report);
+                    }
+
+                    if matches.is_present("report-and-fix") && changed {
+                        eprintln!("{}", fix_summary(&report));
+                    }
+
+                    if !matches.is_present("quiet") {
+                        eprintln!("{}", report.summary());
+                    }
+                }
+
+                let check = matches.is_present("check").then_some(fail_on);
+                let code = exit_code(&report, check, matches.is_present("report-and-fix"));
+                if code != 0 {
+                    process::exit(code);
                 }
             }
         }
@@ -214,12 +930,376 @@ More information is available at: {}",
     block_on(done);
 }
 
+/// A progress callback which draws a bar on stderr, redrawn each time
+/// another percent of the files has been processed. Until every file
+/// has been found a spinner and the number of files processed so far
+/// are drawn instead. The caller ends the line once licensing is done.
+fn progress_bar() -> impl Fn(&str, usize, Option<usize>) + Send + Sync {
+    const WIDTH: usize = 40;
+    const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+    let drawn = AtomicUsize::new(0);
+    move |_, done, total| {
+        let total = match total {
+            Some(total) => total,
+            None => {
+                eprint!("\r{} {}", SPINNER[done % SPINNER.len()], done);
+                return;
+            }
+        };
+
+        // Files finish concurrently, only draw when the bar advances.
+        let percent = done * 100 / total;
+        if drawn.fetch_max(percent + 1, Ordering::SeqCst) > percent {
+            return;
+        }
+
+        let filled = WIDTH * done / total;
+        eprint!(
+            "\r[{}{}] {}/{}",
+            "#".repeat(filled),
+            "-".repeat(WIDTH - filled),
+            done,
+            total
+        );
+    }
+}
+
+/// Print the files this run changed other than by adding a header, when
+/// changed, and those it failed on or skipped for a reason worth telling.
+fn print_run_results(report: &Report, changed: bool, dedupe: bool) {
+    let mut lists = vec![
+        (
+            "Updated the outdated license header of the following files:",
+            if changed { &report.updated[..] } else { &[] },
+        ),
+        (
+            "Reflowed the license header of the following files:",
+            if changed { &report.reflowed[..] } else { &[] },
+        ),
+    ];
+    if dedupe {
+        lists.push((
+            "Removed the duplicate license headers of the following files:",
+            if changed {
+                &report.deduplicated[..]
+            } else {
+                &[]
+            },
+        ));
+    } else {
+        lists.push((
+            "The following files have more than one license header, run with --dedupe to remove the duplicates:",
+            &report.duplicated,
+        ));
+    }
+
+    for (heading, files) in lists.iter() {
+        if files.is_empty() {
+            continue;
+        }
+
+        eprintln!("{}", heading);
+        for file in files.iter() {
+            eprintln!("{}", file);
+        }
+    }
+
+    if !report.errors.is_empty() {
+        eprintln!("Failed to license the following files:");
+        for e in &report.errors {
+            eprintln!("{}: {}", e.file, e.error);
+        }
+    }
+
+    if !report.foreign.is_empty() {
+        eprintln!("Skipped the following files which already have a different license:");
+        for file in &report.foreign {
+            eprintln!("{}", file);
+        }
+    }
+
+    if report.unmatched > 0 {
+        eprintln!(
+            "Skipped {} files which matched no license configuration, run with --verbose to list them.",
+            report.unmatched
+        );
+    }
+
+    if report.uncommentable > 0 {
+        eprintln!(
+            "Skipped {} files whose format does not support comments, run with --verbose to list them.",
+            report.uncommentable
+        );
+    }
+}
+
+/// The exit code of a run which produced report, checked for check when
+/// set. With --report-and-fix the run fails when any file needed a fix,
+/// even though it was fixed, and any run fails when a file could not
+/// be licensed.
+fn exit_code(report: &Report, check: Option<FailOn>, report_and_fix: bool) -> i32 {
+    let failed = check.is_some_and(|fail_on| report.fails(fail_on))
+        || (report_and_fix && report.fails(FailOn::Any))
+        || !report.errors.is_empty();
+    i32::from(failed)
+}
+
+/// The number of files which needed a fix before --report-and-fix fixed
+/// them, followed by what was done to them and how many still fail.
+fn fix_summary(report: &Report) -> String {
+    let changed = report.changed();
+    let still_failing = report
+        .not_licensed
+        .iter()
+        .filter(|f| !changed.contains(&f.as_str()))
+        .count()
+        + report.errors.len();
+    let mut after = vec![
+        format!("Licensed: {}", report.newly_licensed),
+        format!("Updated: {}", report.updated.len()),
+    ];
+    for (name, count) in [
+        ("Deduplicated", report.deduplicated.len()),
+        ("Reflowed", report.reflowed.len()),
+    ] {
+        if count > 0 {
+            after.push(format!("{}: {}", name, count));
+        }
+    }
+    after.push(format!("Still failing: {}", still_failing));
+
+    format!(
+        "Before fixing: {} files needed a fix.\nAfter fixing: {}.",
+        report.not_licensed.len() + report.errors.len(),
+        after.join(", ")
+    )
+}
+
+/// Print the files found by --check under a heading for each kind of
+/// problem.
+fn print_check_results(report: &Report) {
+    let other: Vec<String> = report
+        .not_licensed
+        .iter()
+        .filter(|f| {
+            !report.missing.contains(f)
+                && !report.outdated.contains(f)
+                && !report.duplicated.contains(f)
+                && !report.reflowed.contains(f)
+        })
+        .cloned()
+        .collect();
+    let categories = [
+        (
+            "The following files are missing a license header:",
+            &report.missing,
+        ),
+        (
+            "The following files have an outdated license header:",
+            &report.outdated,
+        ),
+        (
+            "The following files have more than one license header:",
+            &report.duplicated,
+        ),
+        (
+            "The following files have a license header which is wrapped or spaced differently:",
+            &report.reflowed,
+        ),
+        (
+            "The following files have a license header followed by mismatched trailing lines:",
+            &other,
+        ),
+    ];
+
+    for (heading, files) in categories.iter() {
+        if files.is_empty() {
+            continue;
+        }
+
+        eprintln!("{}", heading);
+        for file in files.iter() {
+            eprintln!("{}", file);
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    use licensure::FileError;
+    use testing::TempDir;
+
     #[test]
     fn test_get_project_files() {
-        assert!(get_project_files().len() != 0)
+        assert!(!get_project_files(&[]).is_empty());
+        assert!(get_project_files(&["src/"])
+            .iter()
+            .all(|f| f.starts_with("src/")));
+    }
+
+    #[test]
+    fn test_staged_file() {
+        assert_eq!(
+            Some("src/main.rs"),
+            staged_file("100644 8f1c2a4b 0\tsrc/main.rs")
+        );
+        assert_eq!(None, staged_file("160000 5d3e9f0a 0\tvendor/lib"));
+    }
+
+    /// Run git with args in dir, as a committer named in the test.
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=Licensure",
+                "-c",
+                "user.email=test@example.com",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    #[test]
+    fn test_get_changed_files() {
+        let dir = TempDir::new("changed-files");
+        git(dir.path(), &["init", "-q"]);
+        dir.write("kept.rs", "fn kept() {}\n");
+        dir.write("changed.rs", "fn changed() {}\n");
+        dir.write("deleted.rs", "fn deleted() {}\n");
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "first"]);
+        git(dir.path(), &["tag", "first"]);
+
+        dir.write("changed.rs", "fn changed() { changed() }\n");
+        std::fs::remove_file(dir.join("deleted.rs")).unwrap();
+        git(dir.path(), &["add", "-A"]);
+        git(dir.path(), &["commit", "-q", "-m", "second"]);
+
+        assert_eq!(
+            Ok(vec!["changed.rs".to_string()]),
+            get_changed_files(dir.path(), "first")
+        );
+        assert_eq!(Ok(Vec::new()), get_changed_files(dir.path(), "HEAD"));
+
+        let err = get_changed_files(dir.path(), "no-such-ref").unwrap_err();
+        assert!(err.contains("no-such-ref"));
+    }
+
+    #[test]
+    fn test_get_staged_files() {
+        let dir = TempDir::new("staged-files");
+        git(dir.path(), &["init", "-q"]);
+        dir.write("modified.rs", "fn modified() {}\n");
+        dir.write("deleted.rs", "fn deleted() {}\n");
+        git(dir.path(), &["add", "."]);
+        git(dir.path(), &["commit", "-q", "-m", "first"]);
+
+        dir.write("added.rs", "fn added() {}\n");
+        dir.write("modified.rs", "fn modified() { modified() }\n");
+        std::fs::remove_file(dir.join("deleted.rs")).unwrap();
+        git(dir.path(), &["add", "-A"]);
+
+        let staged = get_staged_files(dir.path(), &[]).unwrap();
+        assert_eq!(
+            vec!["added.rs".to_string(), "modified.rs".to_string()],
+            staged
+        );
+        assert_eq!(
+            Ok(Vec::new()),
+            get_staged_files(dir.path(), &["no-such-dir/"])
+        );
+        assert_eq!(Ok(Vec::new()), get_partly_staged_files(dir.path(), &staged));
+
+        // A change left unstaged would not be part of the commit.
+        dir.write(
+            "modified.rs",
+            "// Copyright\nfn modified() { modified() }\n",
+        );
+        assert_eq!(
+            Ok(vec!["modified.rs".to_string()]),
+            get_partly_staged_files(dir.path(), &staged)
+        );
+    }
+
+    #[test]
+    fn test_get_dir_files() {
+        let files: Vec<String> = get_dir_files("src").collect();
+        assert!(files.contains(&"src/main.rs".to_string()));
+        assert!(!files.iter().any(|f| f.starts_with("target")));
+    }
+
+    #[test]
+    fn test_is_binary() {
+        let dir = TempDir::new("is-binary");
+        assert!(is_binary(Path::new(&dir.write("data", b"text\0more"))));
+        assert!(!is_binary(Path::new(&dir.write("text", "just text"))));
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let mut report = Report {
+            licensed: 2,
+            ..Default::default()
+        };
+        assert_eq!(0, exit_code(&report, Some(FailOn::Any), false));
+        assert_eq!(0, exit_code(&report, None, true));
+
+        // Files which needed a fix fail the run even once fixed.
+        report.not_licensed = vec!["main.rs".to_string()];
+        report.missing = vec!["main.rs".to_string()];
+        report.newly_licensed = 1;
+        assert_eq!(1, exit_code(&report, None, true));
+        assert_eq!(1, exit_code(&report, Some(FailOn::Missing), false));
+        assert_eq!(0, exit_code(&report, Some(FailOn::Outdated), false));
+        assert_eq!(0, exit_code(&report, None, false));
+
+        report.errors.push(FileError {
+            file: "src".to_string(),
+            error: "Is a directory".to_string(),
+        });
+        assert_eq!(1, exit_code(&report, None, false));
+    }
+
+    #[test]
+    fn test_fix_summary() {
+        let report = Report {
+            not_licensed: vec!["a.rs".into(), "b.rs".into(), "c.rs".into()],
+            missing: vec!["a.rs".into()],
+            outdated: vec!["b.rs".into()],
+            updated: vec!["b.rs".into()],
+            newly_licensed: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            "Before fixing: 3 files needed a fix.\nAfter fixing: Licensed: 1, Updated: 1, Still failing: 1.",
+            fix_summary(&report)
+        );
+    }
+
+    #[test]
+    fn test_looks_like_spdx_ident() {
+        assert!(looks_like_spdx_ident("MIT"));
+        assert!(looks_like_spdx_ident("GPL-3.0-or-later"));
+        assert!(looks_like_spdx_ident("(MIT OR Apache-2.0)"));
+        assert!(!looks_like_spdx_ident("My License"));
+        assert!(!looks_like_spdx_ident("MIT/X11"));
+    }
+
+    #[test]
+    fn test_split_file_list() {
+        assert_eq!(
+            vec!["a.rs", "src/b c.rs"],
+            split_file_list("a.rs\n\nsrc/b c.rs\r\n", false)
+        );
+        assert_eq!(
+            vec!["a.rs", "with\nnewline.rs"],
+            split_file_list("a.rs\0with\nnewline.rs\0", true)
+        );
     }
 }
@@ -0,0 +1,191 @@
+use std::ops::Range;
+
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+/// The extension code cells are commented as when the notebook does
+/// not name the file extension of its language.
+const DEFAULT_CODE_EXTENSION: &str = ".py";
+
+/// Whether file is a Jupyter notebook, whose header goes in the source
+/// of its first code or markdown cell.
+pub fn is_notebook(file: &str) -> bool {
+    file.ends_with(".ipynb")
+}
+
+/// The first code or markdown cell of a notebook.
+pub struct Cell {
+    /// The extension of the files the cell is commented like, that of
+    /// the notebook language for code cells and .md for markdown ones.
+    pub extension: String,
+    pub source: String,
+    /// The byte range of the source of the cell in the notebook JSON.
+    range: Range<usize>,
+    /// Whether the source is a list of lines rather than one string.
+    is_lines: bool,
+}
+
+#[derive(Deserialize)]
+struct Notebook<'a> {
+    #[serde(borrow)]
+    cells: Vec<RawCell<'a>>,
+    #[serde(default)]
+    metadata: Metadata,
+}
+
+#[derive(Deserialize)]
+struct RawCell<'a> {
+    cell_type: String,
+    #[serde(borrow)]
+    source: &'a RawValue,
+}
+
+#[derive(Default, Deserialize)]
+struct Metadata {
+    language_info: Option<LanguageInfo>,
+}
+
+#[derive(Deserialize)]
+struct LanguageInfo {
+    file_extension: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Source {
+    Text(String),
+    Lines(Vec<String>),
+}
+
+/// Find the first code or markdown cell of the notebook JSON content,
+/// None when it has neither.
+pub fn first_cell(content: &str) -> Result<Option<Cell>, serde_json::Error> {
+    let notebook: Notebook = serde_json::from_str(content)?;
+    let code_extension = notebook
+        .metadata
+        .language_info
+        .and_then(|info| info.file_extension)
+        .unwrap_or_else(|| DEFAULT_CODE_EXTENSION.to_string());
+
+    let cell = match notebook
+        .cells
+        .into_iter()
+        .find(|cell| cell.cell_type == "code" || cell.cell_type == "markdown")
+    {
+        Some(cell) => cell,
+        None => return Ok(None),
+    };
+
+    let raw = cell.source.get();
+    // The raw source borrows from content, its offset is its position.
+    let start = raw.as_ptr() as usize - content.as_ptr() as usize;
+    let (source, is_lines) = match serde_json::from_str(raw)? {
+        Source::Text(source) => (source, false),
+        Source::Lines(lines) => (lines.concat(), true),
+    };
+
+    Ok(Some(Cell {
+        extension: if cell.cell_type == "code" {
+            code_extension
+        } else {
+            ".md".to_string()
+        },
+        source,
+        range: start..start + raw.len(),
+        is_lines,
+    }))
+}
+
+/// The notebook content with the source of cell replaced by source.
+/// Everything else is kept byte for byte, and a list of lines stays a
+/// list laid out like the original one.
+pub fn with_source(content: &str, cell: &Cell, source: &str) -> String {
+    let replacement = if cell.is_lines {
+        let inner = &content[cell.range.start + 1..cell.range.end - 1];
+        let before_item = &inner[..inner.len() - inner.trim_start().len()];
+        let before_end = &inner[inner.trim_end().len()..];
+        let items: Vec<String> = source
+            .split_inclusive('\n')
+            .map(|line| serde_json::Value::from(line).to_string())
+            .collect();
+        if items.is_empty() {
+            "[]".to_string()
+        } else {
+            format!(
+                "[{}{}{}]",
+                before_item,
+                items.join(&format!(",{}", before_item)),
+                before_end
+            )
+        }
+    } else {
+        serde_json::Value::from(source).to_string()
+    };
+
+    format!(
+        "{}{}{}",
+        &content[..cell.range.start],
+        replacement,
+        &content[cell.range.end..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "raw",
+   "metadata": {},
+   "source": ["raw"]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "metadata": {"tags": ["setup"]},
+   "outputs": [{"name": "stdout", "output_type": "stream", "text": ["1\n"]}],
+   "source": [
+    "import os\n",
+    "print(1)"
+   ]
+  }
+ ],
+ "metadata": {"language_info": {"file_extension": ".jl"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"##;
+
+    #[test]
+    fn test_first_cell() {
+        let cell = first_cell(NOTEBOOK).unwrap().unwrap();
+        assert_eq!(".jl", cell.extension);
+        assert_eq!("import os\nprint(1)", cell.source);
+
+        let licensed = with_source(NOTEBOOK, &cell, "# Licensed\nimport os\nprint(1)");
+        assert_eq!(
+            NOTEBOOK.replace(
+                "[\n    \"import os\\n\",",
+                "[\n    \"# Licensed\\n\",\n    \"import os\\n\","
+            ),
+            licensed
+        );
+        assert_eq!(
+            "# Licensed\nimport os\nprint(1)",
+            first_cell(&licensed).unwrap().unwrap().source
+        );
+
+        let markdown = r##"{"cells": [{"cell_type": "markdown", "source": "# Title"}]}"##;
+        let cell = first_cell(markdown).unwrap().unwrap();
+        assert_eq!(".md", cell.extension);
+        assert_eq!(
+            r##"{"cells": [{"cell_type": "markdown", "source": "<!-- x -->\n# Title"}]}"##,
+            with_source(markdown, &cell, "<!-- x -->\n# Title")
+        );
+
+        assert!(first_cell(r#"{"cells": []}"#).unwrap().is_none());
+        assert!(first_cell("not json").is_err());
+    }
+}
@@ -1,71 +1,412 @@
 use chrono::prelude::*;
 use regex::Regex;
 use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::env;
 use std::fmt;
 
-#[derive(Clone, Deserialize)]
+use crate::comments::Comment;
+use crate::error::LicensureError;
+use crate::git;
+
+/// The template used for licenses with type spdx-short.
+pub const SPDX_SHORT_TEMPLATE: &str =
+    "Copyright [year] [name of author]\nSPDX-License-Identifier: [spdx]\n";
+
+/// The copyright line of composed headers without a copyright_template.
+pub const DEFAULT_COPYRIGHT_TEMPLATE: &str = "Copyright [year] [name of author]";
+
+/// The copyright line of REUSE headers without a copyright_template.
+pub const DEFAULT_REUSE_COPYRIGHT_TEMPLATE: &str =
+    "SPDX-FileCopyrightText: [year] [name of author]";
+
+/// The notice of composed headers without a notice_template.
+pub const DEFAULT_NOTICE_TEMPLATE: &str = "SPDX-License-Identifier: [spdx]";
+
+const PROJECT_TOKEN: &str = "[project]";
+
+/// Substituted with the https URL of the origin remote of the
+/// repository.
+pub const VCS_URL_TOKEN: &str = "[vcs_url]";
+
+/// Replaced with the URL of the full text of the license.
+pub const LICENSE_URL_TOKEN: &str = "[license_url]";
+
+const SPDX_TOKEN: &str = "[spdx]";
+
+const COPYRIGHT_SYMBOL_TOKEN: &str = "[copyright_symbol]";
+
+/// The copyright symbol used when copyright_symbol is not configured.
+const DEFAULT_COPYRIGHT_SYMBOL: &str = "(C)";
+
+/// Matches any of the copyright symbols in place of the current one.
+const COPYRIGHT_SYMBOL_PATTERN: &str = r"(?:\([Cc]\)|©)";
+
+/// The text which follows the listed authors when there are more than
+/// max_authors.
+const DEFAULT_ET_AL: &str = "et al.";
+
+/// A section of the template only kept for some files, which must not
+/// contain another.
+const CONDITIONAL_PATTERN: &str = r"(?s)\[if ([^\]]*)\](.*?)\[endif\]";
+
+/// Matches [env:NAME] and [env:NAME:default] tokens.
+const ENV_TOKEN_PATTERN: &str = r"\[env:([A-Za-z_][A-Za-z0-9_]*)(?::([^\]]*))?\]";
+
+/// Matches any author list in place of the current one, authors are
+/// always rendered on a single line unless the header is wrapped, see
+/// wrapped_authors_pattern.
+const AUTHORS_PATTERN: &str = r"[^\r\n]*?";
+
+/// Matches both a single year and a year range such as 2018-2024.
+const YEAR_PATTERN: &str = r"\d{4}(?:\s*-\s*\d{4})?";
+
+/// Stands in for the spaces of a wrapped header while its outdated
+/// pattern is built, so that they can match a line break as well.
+const SPACE_MARK: char = '\u{e000}';
+
+/// Follows the characters after which wrapping may end a line without
+/// a space, such as the / of and/or, while an outdated pattern is
+/// built.
+const BREAK_MARK: char = '\u{e002}';
+
+/// Stands in for the text of a comment when finding out what a
+/// commenter puts between two lines.
+const PROBE_MARK: &str = "\u{e001}";
+
+/// Stands in for the [copyright_symbol] token while an outdated pattern
+/// is built.
+const SYMBOL_MARK: &str = "\u{e004}";
+
+/// Stands in for the authors of every copyright line while an outdated
+/// pattern is built, so that only they, and not the same text elsewhere
+/// in the header, match any authors.
+const AUTHORS_MARK: &str = "\u{e005}";
+
+/// A field of an author which can appear in the rendered authors.
+#[derive(Clone, Copy, Deserialize, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorField {
+    Name,
+    Email,
+    Orcid,
+    Role,
+}
+
+/// The author fields rendered when author_fields is not configured,
+/// each only appears when the author has it.
+pub const DEFAULT_AUTHOR_FIELDS: &[AuthorField] = &[
+    AuthorField::Name,
+    AuthorField::Email,
+    AuthorField::Orcid,
+    AuthorField::Role,
+];
+
+/// The emails of an author are either a single email or a list of
+/// them.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EmailsConfig {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(from = "EmailsConfig")]
+struct Emails(Vec<String>);
+
+impl From<EmailsConfig> for Emails {
+    fn from(cfg: EmailsConfig) -> Emails {
+        match cfg {
+            EmailsConfig::One(email) => Emails(vec![email]),
+            EmailsConfig::Many(emails) => Emails(emails),
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
 struct CopyrightHolder {
     name: String,
-    email: Option<String>,
+    #[serde(default)]
+    email: Emails,
+    orcid: Option<String>,
+    role: Option<String>,
 }
 
-impl fmt::Display for CopyrightHolder {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut a = self.name.clone();
+impl CopyrightHolder {
+    /// Render the given fields of the author in their order, as Name
+    /// <email> <other email> followed by the ORCID and role in
+    /// parentheses.
+    fn render(&self, fields: &[AuthorField]) -> String {
+        let mut parts = Vec::new();
+        let mut details = Vec::new();
+        for field in fields {
+            match field {
+                AuthorField::Name => parts.push(self.name.clone()),
+                AuthorField::Email => {
+                    for email in &self.email.0 {
+                        parts.push(format!("<{}>", email));
+                    }
+                }
+                AuthorField::Orcid => {
+                    if let Some(orcid) = &self.orcid {
+                        details.push(format!("ORCID: {}", orcid));
+                    }
+                }
+                AuthorField::Role => {
+                    if let Some(role) = &self.role {
+                        details.push(role.clone());
+                    }
+                }
+            }
+        }
 
-        if let Some(email) = &self.email {
-            a.push_str(&format!(" <{}>", email));
+        if !details.is_empty() {
+            parts.push(format!("({})", details.join(", ")));
         }
 
-        write!(f, "{}", a)
+        parts.join(" ")
+    }
+}
+
+impl fmt::Display for CopyrightHolder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(DEFAULT_AUTHOR_FIELDS))
     }
 }
 
-#[derive(Clone, Deserialize)]
-#[serde(from = "Vec<CopyrightHolder>")]
+/// Authors are either listed in the config or the string "from-git"
+/// to take them from the git history of each file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AuthorsConfig {
+    List(Vec<CopyrightHolder>),
+    Source(String),
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(try_from = "AuthorsConfig")]
 pub struct Authors {
     authors: Vec<CopyrightHolder>,
+    from_git: bool,
 }
 
 impl From<Vec<CopyrightHolder>> for Authors {
     fn from(authors: Vec<CopyrightHolder>) -> Authors {
-        Authors { authors }
+        Authors {
+            authors,
+            from_git: false,
+        }
     }
 }
 
-impl fmt::Display for Authors {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl TryFrom<AuthorsConfig> for Authors {
+    type Error = String;
+
+    fn try_from(cfg: AuthorsConfig) -> Result<Authors, String> {
+        match cfg {
+            AuthorsConfig::List(authors) => Ok(Authors::from(authors)),
+            AuthorsConfig::Source(source) if source == "from-git" => Ok(Authors {
+                authors: Vec::new(),
+                from_git: true,
+            }),
+            AuthorsConfig::Source(source) => Err(format!(
+                "unknown authors {}, expected a list of authors or from-git",
+                source
+            )),
+        }
+    }
+}
+
+impl Authors {
+    /// Whether there are no authors, neither listed nor from git.
+    pub fn is_empty(&self) -> bool {
+        self.authors.is_empty() && !self.from_git
+    }
+
+    /// Whether the authors should be taken from git history.
+    pub fn is_from_git(&self) -> bool {
+        self.from_git
+    }
+
+    /// Build the authors of file from its git history, leaving out any
+    /// author whose name or email is in exclude.
+    pub fn from_git(file: &str, exclude: &[String]) -> Authors {
+        Authors::from(
+            git::authors(file)
+                .into_iter()
+                .filter(|a| !exclude.iter().any(|e| *e == a.name || *e == a.email))
+                .map(|a| CopyrightHolder {
+                    name: a.name,
+                    email: Emails(
+                        Some(a.email)
+                            .filter(|e| !e.is_empty())
+                            .into_iter()
+                            .collect(),
+                    ),
+                    orcid: None,
+                    role: None,
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Sort the authors by name, then email, so that the header does
+    /// not depend on the order they were listed or found in.
+    pub fn sort(&mut self) {
+        self.authors
+            .sort_by(|a, b| (&a.name, &a.email.0).cmp(&(&b.name, &b.email.0)));
+    }
+
+    /// Format the authors as a comma separated list of their fields.
+    /// When there are more than max_authors only the first max_authors
+    /// are listed, followed by et_al. A max_authors of 0 lists every
+    /// author.
+    pub fn format(&self, max_authors: usize, et_al: &str, fields: &[AuthorField]) -> String {
         let mut a = String::new();
 
-        for author in &self.authors {
+        let limit = if max_authors == 0 {
+            self.authors.len()
+        } else {
+            max_authors
+        };
+
+        for author in self.authors.iter().take(limit) {
             if !a.is_empty() {
                 a.push_str(", ");
             }
 
-            a.push_str(&author.to_string());
+            a.push_str(&author.render(fields));
         }
 
-        write!(f, "{}", a)
+        if self.authors.len() > limit {
+            a.push(' ');
+            a.push_str(et_al);
+        }
+
+        a
     }
 }
 
-#[derive(Clone)]
+impl fmt::Display for Authors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.format(0, DEFAULT_ET_AL, DEFAULT_AUTHOR_FIELDS)
+        )
+    }
+}
+
+/// A copyright line of its own, for headers which stack more than one
+/// such as a company line followed by a contributors line.
+#[derive(Clone, Default, Deserialize)]
+pub struct Copyright {
+    pub authors: Authors,
+    /// The year of this line, that of the license when None.
+    pub year: Option<String>,
+}
+
+#[derive(Clone, Default)]
 pub struct Context {
     pub ident: String,
+    /// Substituted for [spdx], ident when None.
+    pub spdx_id: Option<String>,
+    /// Everything after the first dot of the name of the licensed file,
+    /// e.g. pb.go, for [if ext == EXT] sections.
+    pub extension: Option<String>,
     pub authors: Authors,
+    /// When not empty the line of the template with [name of author]
+    /// is rendered once for each of these, in place of authors.
+    pub copyrights: Vec<Copyright>,
+    /// Put before the list of authors, e.g. "the", and kept as is in
+    /// outdated headers whatever their authors.
+    pub authors_prefix: Option<String>,
+    /// Put after the list of authors, e.g. "and contributors".
+    pub authors_suffix: Option<String>,
+    pub max_authors: usize,
+    pub et_al: Option<String>,
+    /// Substituted for [copyright_symbol], (C) when None.
+    pub copyright_symbol: Option<String>,
+    /// The fields of each author to render, all of them when None.
+    pub author_fields: Option<Vec<AuthorField>>,
     pub year: Option<String>,
+    pub start_year: Option<String>,
+    pub year_range: bool,
+    /// Leave the year out of the header entirely, removing the [year]
+    /// token along with the whitespace before it.
+    pub omit_year: bool,
     pub unwrap_text: bool,
+    /// Keep the line breaks of the template exactly as written, the
+    /// header is neither unwrapped nor rewrapped to the columns.
+    pub preserve_line_breaks: bool,
+    pub project: Option<String>,
+    /// Substituted for [vcs_url], it is an error to use the token when
+    /// None.
+    pub vcs_url: Option<String>,
+    /// Substituted for [license_url], it is an error to use the token
+    /// when None.
+    pub license_url: Option<String>,
+    /// User defined tokens, substituted for [name].
+    pub tokens: BTreeMap<String, String>,
 }
 
 impl Context {
-    fn get_authors(&self) -> String {
-        self.authors.to_string()
+    fn get_authors(&self, authors: &Authors) -> String {
+        authors.format(
+            self.max_authors,
+            self.et_al.as_deref().unwrap_or(DEFAULT_ET_AL),
+            self.author_fields
+                .as_deref()
+                .unwrap_or(DEFAULT_AUTHOR_FIELDS),
+        )
+    }
+
+    /// The authors along with their prefix and suffix, as [name of
+    /// author] is rendered. rendered, when given, stands in for the
+    /// formatted list of authors.
+    fn get_authors_text(&self, authors: &Authors, rendered: Option<&str>) -> String {
+        let authors = match rendered {
+            Some(rendered) => rendered.to_string(),
+            None => self.get_authors(authors),
+        };
+        let parts: Vec<&str> = [
+            self.authors_prefix.as_deref(),
+            Some(authors.as_str()),
+            self.authors_suffix.as_deref(),
+        ]
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|part| !part.is_empty())
+        .collect();
+        parts.join(" ")
+    }
+
+    /// The years of every copyright line, as they are rendered with
+    /// year for the lines without a year of their own.
+    fn get_copyright_years(&self, year: &str) -> Vec<String> {
+        let mut years = vec![year.to_string()];
+        for copyright in &self.copyrights {
+            years.extend(copyright.year.clone());
+        }
+        years
     }
 
     fn get_year(&self) -> String {
-        match &self.year {
+        let current = match &self.year {
             Some(year) => year.clone(),
             None => format!("{}", Local::now().year()),
+        };
+
+        if !self.year_range {
+            return current;
+        }
+
+        match &self.start_year {
+            Some(start) if *start != current => format!("{}-{}", start, current),
+            _ => current,
         }
     }
 }
@@ -73,6 +414,7 @@ impl Context {
 #[derive(Clone)]
 pub struct Template {
     spdx_template: bool,
+    reuse: bool,
     content: String,
     context: Context,
 }
@@ -81,6 +423,7 @@ impl Template {
     pub fn new(template: &str, context: Context) -> Template {
         Template {
             spdx_template: false,
+            reuse: false,
             content: template.to_string(),
             context,
         }
@@ -91,8 +434,60 @@ impl Template {
         self
     }
 
-    pub fn render(self) -> String {
-        let (year_repl, author_repl, ident_repl) = if self.spdx_template {
+    /// Set for REUSE headers, files which already have another REUSE
+    /// header are then left as they are.
+    pub fn set_reuse(mut self, yes_or_no: bool) -> Template {
+        self.reuse = yes_or_no;
+        self
+    }
+
+    pub fn is_reuse(&self) -> bool {
+        self.reuse
+    }
+
+    pub fn set_project(mut self, project: Option<String>) -> Template {
+        self.context.project = project;
+        self
+    }
+
+    pub fn set_vcs_url(mut self, vcs_url: Option<String>) -> Template {
+        self.context.vcs_url = vcs_url;
+        self
+    }
+
+    pub fn set_license_url(mut self, license_url: Option<String>) -> Template {
+        self.context.license_url = license_url;
+        self
+    }
+
+    /// The license identifier the template is for.
+    pub fn ident(&self) -> &str {
+        &self.context.ident
+    }
+
+    /// Whether the template uses token, for tokens whose value is
+    /// expensive to find.
+    pub fn uses_token(&self, token: &str) -> bool {
+        self.content.contains(token)
+    }
+
+    pub fn set_tokens(mut self, tokens: BTreeMap<String, String>) -> Template {
+        self.context.tokens = tokens;
+        self
+    }
+
+    /// The width to wrap the header to, columns unless the template
+    /// preserves its line breaks.
+    pub fn columns(&self, columns: Option<usize>) -> Option<usize> {
+        if self.context.preserve_line_breaks {
+            None
+        } else {
+            columns
+        }
+    }
+
+    fn replacement_tokens(&self) -> (&str, &str, &str) {
+        if self.spdx_template {
             // Check if it's the Apache license which has a super
             // special format.
             if self.content.contains("[name of copyright owner]") {
@@ -112,11 +507,27 @@ impl Template {
             }
         } else {
             ("[year]", "[name of author]", "[ident]")
-        };
+        }
+    }
 
-        let mut templ = self.content.clone();
+    /// Render the template with year, or without one, and with
+    /// authors in place of the authors of every copyright line when
+    /// given.
+    fn interpolate(
+        &self,
+        year: Option<&str>,
+        copyright_symbol: &str,
+        authors: Option<&str>,
+    ) -> Result<String, LicensureError> {
+        let (year_repl, author_repl, ident_repl) = self.replacement_tokens();
+        // Conditional sections and environment variables are handled
+        // first so that what is left is unwrapped along with the rest
+        // of the template.
+        let mut templ = apply_conditionals(&self.content, self.context.extension.as_deref())
+            .and_then(|templ| replace_env_tokens(&templ, |name| env::var(name).ok()))
+            .map_err(|e| self.error(e))?;
 
-        if self.context.unwrap_text {
+        if self.context.unwrap_text && !self.context.preserve_line_breaks {
             // Some license headers come pre-textwrapped. This regex
             // replacement removes their wrapping while preserving
             // intentional line breaks / empty lines.
@@ -124,11 +535,453 @@ impl Template {
             templ = re.replace_all(&templ, "$char ").to_string();
         }
 
+        if templ.contains(PROJECT_TOKEN) {
+            match &self.context.project {
+                Some(project) => templ = templ.replace(PROJECT_TOKEN, project),
+                None => {
+                    return Err(self.error("it uses the [project] token but no project name is configured, please add a project.name option to your config"));
+                }
+            }
+        }
+
+        if templ.contains(VCS_URL_TOKEN) {
+            match &self.context.vcs_url {
+                Some(vcs_url) => templ = templ.replace(VCS_URL_TOKEN, vcs_url),
+                None => {
+                    return Err(self.error("it uses the [vcs_url] token but no origin remote was found for the git repository, please add one or set project.vcs_url_optional to leave the token blank"));
+                }
+            }
+        }
+
+        if templ.contains(LICENSE_URL_TOKEN) {
+            match &self.context.license_url {
+                Some(license_url) => templ = templ.replace(LICENSE_URL_TOKEN, license_url),
+                None => {
+                    return Err(self.error(format!("it uses the [license_url] token but no URL is known for {}, please add one to license_urls", self.context.ident)));
+                }
+            }
+        }
+
+        for (name, value) in &self.context.tokens {
+            templ = templ.replace(&format!("[{}]", name), value);
+        }
+
+        templ = templ.replace(COPYRIGHT_SYMBOL_TOKEN, copyright_symbol);
+        templ = templ.replace(
+            SPDX_TOKEN,
+            self.context
+                .spdx_id
+                .as_deref()
+                .unwrap_or(&self.context.ident),
+        );
+
+        if !self.context.copyrights.is_empty() {
+            templ = templ
+                .split_inclusive('\n')
+                .map(|line| self.expand_copyrights(line, year_repl, author_repl, year, authors))
+                .collect();
+        }
+
+        templ = match year {
+            Some(year) => templ.replace(year_repl, year),
+            None => remove_token(&templ, year_repl),
+        };
+
         // Perform our substitutions
-        templ
-            .replace(year_repl, &self.context.get_year())
-            .replace(author_repl, &self.context.get_authors())
-            .replace(ident_repl, &self.context.ident)
+        Ok(templ
+            .replace(
+                author_repl,
+                &self
+                    .context
+                    .get_authors_text(&self.context.authors, authors),
+            )
+            .replace(ident_repl, &self.context.ident))
+    }
+
+    /// Render line once for each copyright of the context, one below
+    /// the other, with the year and authors of that copyright. Lines
+    /// without the authors token are kept as they are.
+    fn expand_copyrights(
+        &self,
+        line: &str,
+        year_repl: &str,
+        author_repl: &str,
+        year: Option<&str>,
+        authors: Option<&str>,
+    ) -> String {
+        if !line.contains(author_repl) {
+            return line.to_string();
+        }
+
+        let text = line.trim_end_matches('\n');
+        let lines: Vec<String> = self
+            .context
+            .copyrights
+            .iter()
+            .map(|copyright| {
+                let text = match year {
+                    Some(year) => {
+                        text.replace(year_repl, copyright.year.as_deref().unwrap_or(year))
+                    }
+                    None => remove_token(text, year_repl),
+                };
+                text.replace(
+                    author_repl,
+                    &self.context.get_authors_text(&copyright.authors, authors),
+                )
+            })
+            .collect();
+        format!("{}{}", lines.join("\n"), &line[text.len()..])
+    }
+
+    /// An error rendering this template, for the reason message.
+    fn error(&self, message: impl fmt::Display) -> LicensureError {
+        LicensureError::Template {
+            ident: self.context.ident.clone(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn render(&self) -> Result<String, LicensureError> {
+        let symbol = self
+            .context
+            .copyright_symbol
+            .as_deref()
+            .unwrap_or(DEFAULT_COPYRIGHT_SYMBOL);
+        if self.context.omit_year {
+            return self.interpolate(None, symbol, None);
+        }
+
+        self.interpolate(Some(&self.context.get_year()), symbol, None)
+    }
+
+    /// Build a regex which matches this template once commented but
+    /// with any year (or year range) and any authors in place of the
+    /// current ones. This finds headers that were generated by a
+    /// previous run in an earlier year or before the authors changed.
+    /// With several copyright lines each of them matches this way.
+    /// When the year is omitted headers with or without a year match.
+    /// Any copyright symbol matches in place of [copyright_symbol].
+    ///
+    /// When columns is set headers wrapped at any column width match,
+    /// so that changing columns does not license files twice.
+    ///
+    /// Case insensitive comment markers, like REM, match in any case.
+    ///
+    /// The line endings of the trailing lines of the commenter match
+    /// too when present, so that replacing a match with the header
+    /// leaves at least that many blank lines after it.
+    pub fn outdated_license_pattern(
+        &self,
+        commenter: &dyn Comment,
+        columns: Option<usize>,
+    ) -> Result<Regex, LicensureError> {
+        let source = self.outdated_license_source(commenter, columns)?;
+        Ok(Regex::new(&source).expect("escaped license header should always be a valid regex"))
+    }
+
+    /// The source of the regex built by outdated_license_pattern, so
+    /// that the compiled regex can be reused by files with the same
+    /// pattern.
+    pub fn outdated_license_source(
+        &self,
+        commenter: &dyn Comment,
+        columns: Option<usize>,
+    ) -> Result<String, LicensureError> {
+        let year = self.context.get_year();
+        let years = self.context.get_copyright_years(&year);
+        let text = self.interpolate(Some(&year), SYMBOL_MARK, Some(AUTHORS_MARK))?;
+        if columns.is_none() {
+            let header = commenter.comment(&text, None);
+            let pattern = build_outdated_pattern(
+                header.trim_end_matches(['\n', '\r', ' ']),
+                &years,
+                self.context.omit_year,
+                None,
+            );
+            let pattern = ignore_marker_case(pattern, commenter);
+            return Ok(with_trailing_lines(pattern, &header));
+        }
+
+        // Comment the header unwrapped with the places it could be
+        // wrapped at marked, each of them may then match a line break.
+        let header = commenter.comment(&mark_breaks(&text), None);
+        let pattern = build_outdated_pattern(
+            header.trim_end_matches(['\n', '\r', ' ']),
+            &years
+                .iter()
+                .map(|year| mark_breaks(year))
+                .collect::<Vec<_>>(),
+            self.context.omit_year,
+            Some(&line_break(commenter)),
+        );
+        let pattern = ignore_marker_case(pattern, commenter);
+        Ok(with_trailing_lines(pattern, &header))
+    }
+
+    /// The source of a regex which matches this template once commented
+    /// with the current year and authors, however its lines are wrapped
+    /// and however much whitespace separates its words. Headers which
+    /// match it only differ from the expected one in their formatting.
+    pub fn reflow_license_source(&self, commenter: &dyn Comment) -> Result<String, LicensureError> {
+        let header = commenter.comment(&mark_breaks(&self.render()?), None);
+        let pattern = allow_line_breaks(
+            flexible_runs(&regex::escape(header.trim_end_matches(['\n', '\r', ' ']))),
+            &line_break(commenter),
+        );
+        Ok(ignore_marker_case(
+            pattern.replace('\n', "\r?\n"),
+            commenter,
+        ))
+    }
+}
+
+/// The template of a header made of the copyright line followed by the
+/// notice, each on its own line.
+pub fn compose_template(copyright: &str, notice: &str) -> String {
+    format!("{}\n{}\n", copyright.trim_end(), notice.trim_end())
+}
+
+/// Keep the text of each [if ext == EXT]...[endif] section of templ
+/// when extension is EXT or ends with .EXT, so that a pb.go file
+/// matches both pb.go and go, and drop the other sections.
+fn apply_conditionals(templ: &str, extension: Option<&str>) -> Result<String, String> {
+    let re = Regex::new(CONDITIONAL_PATTERN).unwrap();
+    let mut unknown = None;
+    let applied = re.replace_all(templ, |caps: &regex::Captures| {
+        let condition = caps[1].split_once("==").map(|(k, v)| (k.trim(), v.trim()));
+        match condition {
+            Some(("ext", expected)) if !expected.is_empty() => {
+                let suffix = format!(".{}", expected);
+                if extension.is_some_and(|ext| ext == expected || ext.ends_with(&suffix)) {
+                    caps[2].to_string()
+                } else {
+                    String::new()
+                }
+            }
+            _ => {
+                unknown.get_or_insert_with(|| caps[1].to_string());
+                String::new()
+            }
+        }
+    });
+
+    if let Some(condition) = unknown {
+        return Err(format!(
+            "unknown condition [if {}], only [if ext == EXT] is supported",
+            condition
+        ));
+    }
+
+    if applied.contains("[if ") || applied.contains("[endif]") {
+        return Err(
+            "every [if ...] needs a matching [endif] and they cannot be nested".to_string(),
+        );
+    }
+
+    Ok(applied.to_string())
+}
+
+/// Replace [env:NAME] tokens in templ with the value lookup returns
+/// for NAME, or the default given as [env:NAME:default] when there is
+/// none.
+fn replace_env_tokens<F>(templ: &str, lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let re = Regex::new(ENV_TOKEN_PATTERN).unwrap();
+    let mut missing = None;
+    let replaced = re.replace_all(templ, |caps: &regex::Captures| {
+        let name = &caps[1];
+        match lookup(name).or_else(|| caps.get(2).map(|d| d.as_str().to_string())) {
+            Some(value) => value,
+            None => {
+                missing.get_or_insert_with(|| name.to_string());
+                String::new()
+            }
+        }
+    });
+
+    match missing {
+        Some(name) => Err(format!(
+            "the environment variable {} used by [env:{}] is not set",
+            name, name
+        )),
+        None => Ok(replaced.to_string()),
+    }
+}
+
+/// Remove token from templ along with the spaces before it, or after it
+/// when it starts a line, so that no double space is left behind.
+fn remove_token(templ: &str, token: &str) -> String {
+    let token = regex::escape(token);
+    let re = Regex::new(&format!("[ \t]+{0}|{0}[ \t]*", token)).unwrap();
+    re.replace_all(templ, "").to_string()
+}
+
+/// The text commenter puts between two lines of a comment without its
+/// trailing spaces, e.g. "\n#" for a # line comment.
+fn line_break(commenter: &dyn Comment) -> String {
+    let probe = commenter.comment(&format!("{0}\n{0}", PROBE_MARK), None);
+    probe
+        .split(PROBE_MARK)
+        .nth(1)
+        .unwrap_or("\n")
+        .trim_end_matches([' ', '\t'])
+        .to_string()
+}
+
+/// Let the case insensitive comment marker of commenter, if any, match
+/// in any case where it starts a line of the outdated header pattern.
+fn ignore_marker_case(pattern: String, commenter: &dyn Comment) -> String {
+    let marker = match commenter.case_insensitive_marker() {
+        Some(marker) => regex::escape(marker),
+        None => return pattern,
+    };
+    let starts = Regex::new(&format!("(^|\n){}", marker)).unwrap();
+    starts
+        .replace_all(&pattern, format!("${{1}}(?i:{})", marker).as_str())
+        .to_string()
+}
+
+/// Mark the places s could be wrapped at, its spaces with SPACE_MARK
+/// and after each slash or dash with BREAK_MARK.
+fn mark_breaks(s: &str) -> String {
+    s.replace(' ', &SPACE_MARK.to_string())
+        .replace('/', &format!("/{}", BREAK_MARK))
+        .replace('-', &format!("-{}", BREAK_MARK))
+}
+
+/// Let the places of pattern marked by mark_breaks also be line_break,
+/// the text between two lines of the comment, with any spaces around
+/// it. A marked space may also be any run of spaces.
+fn allow_line_breaks(pattern: String, line_break: &str) -> String {
+    let line_break = format!("[ \t]*{}[ \t]*", regex::escape(line_break));
+    pattern
+        .replace(SPACE_MARK, &format!("(?:[ \t]+|{})", line_break))
+        .replace(BREAK_MARK, &format!("(?:{})?", line_break))
+}
+
+/// Let each run of a repeated punctuation character in the escaped
+/// pattern, such as a rule line of the header, be of any length as
+/// their width follows that of the header.
+fn flexible_runs(pattern: &str) -> String {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        }
+        let end = chars.peek().map_or(pattern.len(), |(i, _)| *i);
+        tokens.push(&pattern[start..end]);
+    }
+
+    let mut flexible = String::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let run = tokens[i..].iter().take_while(|t| **t == tokens[i]).count();
+        let is_punctuation = tokens[i]
+            .chars()
+            .last()
+            .is_some_and(|c| c.is_ascii_punctuation());
+        if run >= 3 && is_punctuation {
+            flexible.push_str(&format!("{}{{3,}}", tokens[i]));
+        } else {
+            flexible.push_str(&tokens[i..i + run].concat());
+        }
+        i += run;
+    }
+
+    flexible
+}
+
+/// Let pattern, built from header without its trailing lines, also
+/// match up to as many line endings after it as header ends with, so
+/// that the trailing lines of the commenter are part of the match.
+fn with_trailing_lines(pattern: String, header: &str) -> String {
+    let trailing = &header[header.trim_end_matches(['\n', '\r', ' ']).len()..];
+    match trailing.matches('\n').count() {
+        0 => pattern,
+        n => format!("{}(?:[ \t]*\r?\n){{0,{}}}", pattern, n),
+    }
+}
+
+/// Build the pattern for outdated_license_pattern from header, rendered
+/// with years, those of each of its copyright lines, and AUTHORS_MARK
+/// in place of their authors. When year_optional is set the years,
+/// along with the spaces next to them, may also be missing. When
+/// line_break is given the places marked with SPACE_MARK and BREAK_MARK
+/// may also be a line break of the comment, letting the header be
+/// wrapped differently.
+fn build_outdated_pattern(
+    header: &str,
+    years: &[String],
+    year_optional: bool,
+    line_break: Option<&str>,
+) -> String {
+    let mut pattern = flexible_runs(&regex::escape(header));
+    for year in longest_first(years) {
+        pattern = build_year_varying_regex(pattern, year, year_optional);
+    }
+
+    let authors = match line_break {
+        Some(line_break) => {
+            pattern = allow_line_breaks(pattern, line_break);
+            wrapped_authors_pattern(line_break)
+        }
+        None => AUTHORS_PATTERN.to_string(),
+    };
+
+    // Authors ending the header take the rest of its last line, as
+    // nothing after them would stop a lazy match from being empty.
+    pattern = match pattern.strip_suffix(AUTHORS_MARK) {
+        Some(pattern) => format!("{}[^\r\n]*", pattern),
+        None => pattern,
+    };
+    pattern = pattern
+        .replace(AUTHORS_MARK, &authors)
+        .replace(SYMBOL_MARK, COPYRIGHT_SYMBOL_PATTERN);
+
+    // Allow the header to have been written with either line ending.
+    pattern.replace('\n', "\r?\n")
+}
+
+/// Like AUTHORS_PATTERN but also matching the line breaks of the
+/// comment, with any spaces around them, as a long author list is
+/// wrapped over several lines along with the rest of the header.
+fn wrapped_authors_pattern(line_break: &str) -> String {
+    format!(r"(?:[^\r\n]|[ \t]*{}[ \t]*)*?", regex::escape(line_break))
+}
+
+/// The distinct non empty strings of values, longest first.
+fn longest_first(values: &[String]) -> Vec<&str> {
+    let mut values: Vec<&str> = values
+        .iter()
+        .map(String::as_str)
+        .filter(|value| !value.is_empty())
+        .collect();
+    values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+    values.dedup();
+    values
+}
+
+/// Let year match any year, single or a range, in pattern, or no year
+/// at all along with the spaces next to it when year_optional is set.
+fn build_year_varying_regex(pattern: String, year: &str, year_optional: bool) -> String {
+    let year = regex::escape(year);
+    if year_optional {
+        let re = Regex::new(&format!(
+            "[ \t{1}]+{0}|{0}[ \t{1}]*",
+            regex::escape(&year),
+            SPACE_MARK
+        ))
+        .unwrap();
+        re.replace_all(&pattern, |caps: &regex::Captures| {
+            format!("(?:{})?", caps[0].replace(&year, YEAR_PATTERN))
+        })
+        .to_string()
+    } else {
+        pattern.replace(&year, YEAR_PATTERN)
     }
 }
 
@@ -136,6 +989,21 @@ impl Template {
 mod tests {
     use super::*;
 
+    fn build_outdated_regex(
+        header: &str,
+        year: &str,
+        year_optional: bool,
+        line_break: Option<&str>,
+    ) -> Regex {
+        Regex::new(&build_outdated_pattern(
+            header,
+            &[year.to_string()],
+            year_optional,
+            line_break,
+        ))
+        .unwrap()
+    }
+
     #[test]
     fn test_substitution_at_end_of_line() {
         let context = Context {
@@ -143,10 +1011,11 @@ mod tests {
             authors: Authors::from(vec![]),
             year: Some(String::from("2020")),
             unwrap_text: true,
+            ..Default::default()
         };
         let template = Template::new("License [year]\ntext", context);
         let expected = String::from("License 2020 text");
-        assert_eq!(expected, template.render())
+        assert_eq!(expected, template.render().unwrap())
     }
 
     #[test]
@@ -155,14 +1024,16 @@ mod tests {
             ident: String::from("test"),
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
-                email: Some("chasinglogic@gmail.com".to_string()),
+                email: Emails(vec!["chasinglogic@gmail.com".to_string()]),
+                ..Default::default()
             }]),
             year: Some(String::from("2020")),
             unwrap_text: true,
+            ..Default::default()
         };
         let template = Template::new("Copyright (C) [year] [name of author] This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General Public License as published by the Free Software Foundation, version 3. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more details. You should have received a copy of the GNU Affero General Public License along with this program. If not, see <https://www.gnu.org/licenses/>", context);
         let expected = String::from("Copyright (C) 2020 Mathew Robinson <chasinglogic@gmail.com> This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General Public License as published by the Free Software Foundation, version 3. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more details. You should have received a copy of the GNU Affero General Public License along with this program. If not, see <https://www.gnu.org/licenses/>");
-        assert_eq!(expected, template.render())
+        assert_eq!(expected, template.render().unwrap())
     }
 
     #[test]
@@ -171,10 +1042,12 @@ mod tests {
             ident: String::from("test"),
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
-                email: Some("chasinglogic@gmail.com".to_string()),
+                email: Emails(vec!["chasinglogic@gmail.com".to_string()]),
+                ..Default::default()
             }]),
             year: Some(String::from("2020")),
             unwrap_text: true,
+            ..Default::default()
         };
         let template = Template::new(
             "Copyright (C) [year] [name of author] This
@@ -189,7 +1062,7 @@ this program. If not, see <https://www.gnu.org/licenses/>",
             context,
         );
         let expected = String::from("Copyright (C) 2020 Mathew Robinson <chasinglogic@gmail.com> This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General Public License as published by the Free Software Foundation, version 3. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more details. You should have received a copy of the GNU Affero General Public License along with this program. If not, see <https://www.gnu.org/licenses/>");
-        assert_eq!(expected, template.render())
+        assert_eq!(expected, template.render().unwrap())
     }
 
     #[test]
@@ -198,10 +1071,12 @@ this program. If not, see <https://www.gnu.org/licenses/>",
             ident: String::from("test"),
             authors: Authors::from(vec![CopyrightHolder {
                 name: "Mathew Robinson".to_string(),
-                email: Some("chasinglogic@gmail.com".to_string()),
+                email: Emails(vec!["chasinglogic@gmail.com".to_string()]),
+                ..Default::default()
             }]),
             year: Some(String::from("2020")),
             unwrap_text: true,
+            ..Default::default()
         };
         let template = Template::new(
             "Copyright (C) [year] [name of author] This
@@ -218,6 +1093,805 @@ this program. If not, see <https://www.gnu.org/licenses/>",
         );
         let expected = String::from("Copyright (C) 2020 Mathew Robinson <chasinglogic@gmail.com> This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General Public License as published by the 
 Free Software Foundation, version 3. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License for more details. You should have received a copy of the GNU Affero General Public License along with this program. If not, see <https://www.gnu.org/licenses/>");
-        assert_eq!(expected, template.render())
+        assert_eq!(expected, template.render().unwrap())
+    }
+
+    #[test]
+    fn test_project_substitution() {
+        let context = Context {
+            ident: String::from("test"),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new("This file is part of [project].", context.clone());
+        let err = template.render().err().unwrap();
+        assert!(matches!(err, LicensureError::Template { ref ident, .. } if ident == "test"));
+        assert!(err.to_string().contains("project.name"));
+
+        let template = template.set_project(Some("Licensure".to_string()));
+        assert_eq!(
+            "This file is part of Licensure.",
+            template.render().unwrap()
+        );
+
+        let template = Template::new("No project here [year].", context);
+        assert_eq!("No project here 2020.", template.render().unwrap());
+    }
+
+    #[test]
+    fn test_vcs_url_substitution() {
+        let context = Context {
+            ident: String::from("test"),
+            ..Default::default()
+        };
+        let template = Template::new("Source: [vcs_url]", context);
+        assert!(template.uses_token(VCS_URL_TOKEN));
+        let err = template.render().err().unwrap();
+        assert!(matches!(err, LicensureError::Template { ref ident, .. } if ident == "test"));
+        assert!(err.to_string().contains("[vcs_url]"));
+
+        let template = template.set_vcs_url(Some(
+            "https://github.com/chasinglogic/licensure".to_string(),
+        ));
+        assert_eq!(
+            "Source: https://github.com/chasinglogic/licensure",
+            template.render().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_license_url_substitution() {
+        let context = Context {
+            ident: String::from("Custom-1.0"),
+            ..Default::default()
+        };
+        let template = Template::new("See [license_url]", context);
+        let err = template.render().err().unwrap();
+        assert!(matches!(err, LicensureError::Template { .. }));
+        assert!(err.to_string().contains("no URL is known for Custom-1.0"));
+
+        let template = template.set_license_url(Some("https://example.com/LICENSE".to_string()));
+        assert_eq!(
+            "See https://example.com/LICENSE",
+            template.render().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_spdx_short_template() {
+        let context = Context {
+            ident: String::from("MIT"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: "Mathew Robinson".to_string(),
+                email: Emails::default(),
+                ..Default::default()
+            }]),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new(SPDX_SHORT_TEMPLATE, context);
+        assert_eq!(
+            "Copyright 2020 Mathew Robinson\nSPDX-License-Identifier: MIT\n",
+            template.render().unwrap()
+        );
+
+        let commenter = crate::comments::LineComment::new("//");
+        let re = template.outdated_license_pattern(&commenter, None).unwrap();
+        assert!(re.is_match(
+            "// Copyright 2018 Mathew Robinson\n// SPDX-License-Identifier: MIT\n\nfn main() {}\n"
+        ));
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_rust_doc() {
+        let context = Context {
+            ident: String::from("MIT"),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new(SPDX_SHORT_TEMPLATE, context);
+        let commenter = crate::comments::LineComment::rust_doc(true);
+        let re = template.outdated_license_pattern(&commenter, None).unwrap();
+        assert!(re.is_match("//! Copyright 2018 \n//! SPDX-License-Identifier: MIT\n"));
+        assert!(!re.is_match("// Copyright 2018 \n// SPDX-License-Identifier: MIT\n"));
+    }
+
+    #[test]
+    fn test_year_range() {
+        let context = Context {
+            year: Some(String::from("2024")),
+            start_year: Some(String::from("2018")),
+            year_range: true,
+            ..Default::default()
+        };
+        assert_eq!("2018-2024", context.get_year());
+    }
+
+    #[test]
+    fn test_year_range_collapses_same_year() {
+        let context = Context {
+            year: Some(String::from("2024")),
+            start_year: Some(String::from("2024")),
+            year_range: true,
+            ..Default::default()
+        };
+        assert_eq!("2024", context.get_year());
+    }
+
+    #[test]
+    fn test_year_varying_regex() {
+        let re = build_outdated_regex("# Copyright (C) 2018-2024 Me", "2018-2024", false, None);
+        assert!(re.is_match("# Copyright (C) 2018-2024 Me"));
+        assert!(re.is_match("# Copyright (C) 2019 Me"));
+        assert!(re.is_match("# Copyright (C) 2015-2020 Me"));
+        assert!(!re.is_match("# Copyright (C) Me"));
+    }
+
+    #[test]
+    fn test_author_varying_regex() {
+        let re = build_outdated_regex(
+            &format!("# Copyright 2020 {}\n# All rights reserved.", AUTHORS_MARK),
+            "2020",
+            false,
+            None,
+        );
+        assert!(re.is_match("# Copyright 2020 Me\n# All rights reserved."));
+        assert!(re.is_match("# Copyright 2019 Someone <a@b.c>\n# All rights reserved."));
+        assert!(!re.is_match("# Copyright 2019\n# Me\n# All rights reserved."));
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_author_slot() {
+        let context = Context {
+            ident: String::from("MIT"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: "MIT".to_string(),
+                ..Default::default()
+            }]),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let commenter = crate::comments::LineComment::new("#");
+        let template = Template::new(
+            "Copyright [year] [name of author]. Licensed under the MIT license.",
+            context.clone(),
+        );
+        let re = template.outdated_license_pattern(&commenter, None).unwrap();
+        assert!(re.is_match("# Copyright 2019 Acme Corp. Licensed under the MIT license."));
+        assert!(
+            !re.is_match("# Copyright 2019 Acme Corp. Licensed under the GPL-3.0-only license.")
+        );
+
+        // Authors ending the header are matched whole rather than left
+        // after the match.
+        let template = Template::new("Copyright [year] [name of author]", context);
+        let re = template.outdated_license_pattern(&commenter, None).unwrap();
+        let found = re.find("# Copyright 2019 Jane Doe\nprint(1)\n").unwrap();
+        assert_eq!("# Copyright 2019 Jane Doe", found.as_str().trim_end());
+    }
+
+    #[test]
+    fn test_year_varying_regex_line_endings() {
+        let re = build_outdated_regex("# Copyright 2020\n# Me", "2020", false, None);
+        assert!(re.is_match("# Copyright 2019\n# Me"));
+        assert!(re.is_match("# Copyright 2019\r\n# Me"));
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_html() {
+        let context = Context {
+            ident: String::from("MIT"),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new(SPDX_SHORT_TEMPLATE, context);
+        let commenter = crate::comments::HtmlComment::new();
+        let re = template.outdated_license_pattern(&commenter, None).unwrap();
+        assert!(re.is_match("<!--\nCopyright 2018 \nSPDX-License-Identifier: MIT\n-->\n<p></p>"));
+    }
+
+    #[test]
+    fn test_authors_format() {
+        let authors = Authors::from(
+            ["A", "B", "C"]
+                .iter()
+                .map(|name| CopyrightHolder {
+                    name: name.to_string(),
+                    email: Emails::default(),
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(
+            "A, B, C",
+            authors.format(0, "et al.", DEFAULT_AUTHOR_FIELDS)
+        );
+        assert_eq!(
+            "A, B, C",
+            authors.format(3, "et al.", DEFAULT_AUTHOR_FIELDS)
+        );
+        assert_eq!(
+            "A, B et al.",
+            authors.format(2, "et al.", DEFAULT_AUTHOR_FIELDS)
+        );
+        assert_eq!(
+            "A and contributors",
+            authors.format(1, "and contributors", DEFAULT_AUTHOR_FIELDS)
+        );
+    }
+
+    #[test]
+    fn test_author_fields() {
+        let authors: Authors = serde_yaml::from_str(
+            "- name: Jane Doe\n  email: jane@example.com\n  orcid: 0000-0002-1825-0097\n  role: Maintainer\n- name: John Doe\n  email: john@example.com\n",
+        )
+        .unwrap();
+        assert_eq!(
+            "Jane Doe <jane@example.com> (ORCID: 0000-0002-1825-0097, Maintainer), John Doe <john@example.com>",
+            authors.format(0, DEFAULT_ET_AL, DEFAULT_AUTHOR_FIELDS)
+        );
+        assert_eq!(
+            "Jane Doe (ORCID: 0000-0002-1825-0097), John Doe",
+            authors.format(0, DEFAULT_ET_AL, &[AuthorField::Name, AuthorField::Orcid])
+        );
+    }
+
+    #[test]
+    fn test_sort_authors() {
+        let mut authors: Authors = serde_yaml::from_str(
+            "- name: Zoe\n- name: Adam\n  email: b@example.com\n- name: Adam\n  email: a@example.com\n",
+        )
+        .unwrap();
+        assert_eq!(
+            "Zoe, Adam <b@example.com>, Adam <a@example.com>",
+            authors.format(0, DEFAULT_ET_AL, DEFAULT_AUTHOR_FIELDS)
+        );
+        authors.sort();
+        assert_eq!(
+            "Adam <a@example.com>, Adam <b@example.com>, Zoe",
+            authors.format(0, DEFAULT_ET_AL, DEFAULT_AUTHOR_FIELDS)
+        );
+    }
+
+    #[test]
+    fn test_multiple_emails() {
+        let authors: Authors = serde_yaml::from_str(
+            "- name: Jane Doe\n  email: [jane@example.com, jane@work.example.com]\n- name: John Doe\n  email: john@example.com\n- name: Bot\n",
+        )
+        .unwrap();
+        assert_eq!(
+            "Jane Doe <jane@example.com> <jane@work.example.com>, John Doe <john@example.com>, Bot",
+            authors.format(0, DEFAULT_ET_AL, DEFAULT_AUTHOR_FIELDS)
+        );
+    }
+
+    #[test]
+    fn test_max_authors_substitution() {
+        let context = Context {
+            authors: Authors::from(vec![
+                CopyrightHolder {
+                    name: "Mathew Robinson".to_string(),
+                    email: Emails::default(),
+                    ..Default::default()
+                },
+                CopyrightHolder {
+                    name: "Someone Else".to_string(),
+                    email: Emails::default(),
+                    ..Default::default()
+                },
+            ]),
+            max_authors: 1,
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new("Copyright [year] [name of author]", context);
+        assert_eq!(
+            "Copyright 2020 Mathew Robinson et al.",
+            template.render().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_authors_prefix_and_suffix() {
+        let context = Context {
+            ident: String::from("MIT"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: "Acme Inc.".to_string(),
+                ..Default::default()
+            }]),
+            authors_prefix: Some("the".to_string()),
+            authors_suffix: Some("and contributors".to_string()),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new("Copyright [year] [name of author]", context);
+        assert_eq!(
+            "Copyright 2020 the Acme Inc. and contributors",
+            template.render().unwrap()
+        );
+
+        let commenter = crate::comments::LineComment::new("#");
+        for columns in [None, Some(80)].iter().copied() {
+            let re = template
+                .outdated_license_pattern(&commenter, columns)
+                .unwrap();
+            assert!(re.is_match("# Copyright 2018 the Someone Else and contributors"));
+            assert!(!re.is_match("# Copyright 2018 Someone Else"));
+        }
+    }
+
+    #[test]
+    fn test_copyrights() {
+        let holder = |name: &str| {
+            Authors::from(vec![CopyrightHolder {
+                name: name.to_string(),
+                ..Default::default()
+            }])
+        };
+        let context = Context {
+            ident: String::from("MIT"),
+            copyrights: vec![
+                Copyright {
+                    authors: holder("Acme Inc."),
+                    year: Some(String::from("2015-2020")),
+                },
+                Copyright {
+                    authors: holder("Jane Doe"),
+                    year: None,
+                },
+            ],
+            authors_suffix: Some("and contributors".to_string()),
+            year: Some(String::from("2024")),
+            ..Default::default()
+        };
+        let template = Template::new(
+            "Copyright [year] [name of author]\nUse of this source code is governed by the [ident] license.",
+            context,
+        );
+        assert_eq!(
+            "Copyright 2015-2020 Acme Inc. and contributors\nCopyright 2024 Jane Doe and contributors\nUse of this source code is governed by the MIT license.",
+            template.render().unwrap()
+        );
+
+        let commenter = crate::comments::LineComment::new("#");
+        for columns in [None, Some(80)].iter().copied() {
+            let re = template
+                .outdated_license_pattern(&commenter, columns)
+                .unwrap();
+            assert!(re.is_match("# Copyright 2015 Acme Inc. and contributors\n# Copyright 2019 Someone Else and contributors\n# Use of this source code is governed by the MIT license."));
+            assert!(!re.is_match("# Copyright 2015 Acme Inc. and contributors\n# Use of this source code is governed by the MIT license."));
+        }
+    }
+
+    #[test]
+    fn test_authors_config() {
+        let authors: Authors = serde_yaml::from_str("from-git").unwrap();
+        assert!(authors.is_from_git());
+
+        let authors: Authors = serde_yaml::from_str("[{name: Me}]").unwrap();
+        assert!(!authors.is_from_git());
+        assert_eq!("Me", authors.to_string());
+
+        assert!(serde_yaml::from_str::<Authors>("from-svn").is_err());
+    }
+
+    #[test]
+    fn test_replace_env_tokens() {
+        let lookup = |name: &str| match name {
+            "COMPANY" => Some("Acme Widgets Inc".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            Ok("Copyright Acme Widgets Inc, Default Co".to_string()),
+            replace_env_tokens("Copyright [env:COMPANY], [env:UNSET:Default Co]", lookup)
+        );
+        assert_eq!(
+            Ok("Copyright Acme Widgets Inc".to_string()),
+            replace_env_tokens("Copyright [env:COMPANY:Other]", lookup)
+        );
+        assert!(replace_env_tokens("Copyright [env:UNSET]", lookup).is_err());
+
+        let context = Context {
+            ident: String::from("MIT"),
+            ..Default::default()
+        };
+        let err = Template::new("Copyright [env:LICENSURE_TEST_UNSET]", context)
+            .render()
+            .err()
+            .unwrap();
+        assert!(matches!(err, LicensureError::Template { .. }));
+        assert!(err.to_string().contains("LICENSURE_TEST_UNSET"));
+    }
+
+    #[test]
+    fn test_compose_template() {
+        assert_eq!(
+            SPDX_SHORT_TEMPLATE,
+            compose_template(DEFAULT_COPYRIGHT_TEMPLATE, DEFAULT_NOTICE_TEMPLATE)
+        );
+
+        let context = Context {
+            ident: String::from("MIT"),
+            year: Some(String::from("2020")),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: String::from("Me"),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+        let template = Template::new(
+            &compose_template(
+                "Copyright [copyright_symbol] [year] [name of author]\n",
+                "Licensed under the [ident] license",
+            ),
+            context,
+        );
+        assert_eq!(
+            "Copyright (C) 2020 Me\nLicensed under the MIT license\n",
+            template.render().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_apply_conditionals() {
+        let templ = "Copyright Me\n[if ext == go]Code generated. DO NOT EDIT.\n[endif]";
+        assert_eq!(
+            Ok("Copyright Me\nCode generated. DO NOT EDIT.\n".to_string()),
+            apply_conditionals(templ, Some("pb.go"))
+        );
+        assert_eq!(
+            Ok("Copyright Me\nCode generated. DO NOT EDIT.\n".to_string()),
+            apply_conditionals(&templ.replace("go]", "pb.go]"), Some("pb.go"))
+        );
+        assert_eq!(
+            Ok("Copyright Me\n".to_string()),
+            apply_conditionals(templ, Some("rs"))
+        );
+        assert_eq!(
+            Ok("Copyright Me\n".to_string()),
+            apply_conditionals(templ, None)
+        );
+        assert!(apply_conditionals("[if lang == go]x[endif]", Some("go")).is_err());
+        assert!(apply_conditionals("[if ext == go]x", Some("go")).is_err());
+
+        let context = Context {
+            ident: String::from("MIT"),
+            extension: Some(String::from("go")),
+            ..Default::default()
+        };
+        assert!(Template::new("[if ext != go]x[endif]", context)
+            .render()
+            .is_err());
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_double_dash() {
+        let context = Context {
+            ident: String::from("MIT"),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new(SPDX_SHORT_TEMPLATE, context);
+        let commenter = crate::comments::LineComment::new("--");
+        let re = template.outdated_license_pattern(&commenter, None).unwrap();
+        assert!(re.is_match("-- Copyright 2018 \n-- SPDX-License-Identifier: MIT\nSELECT 1;\n"));
+        assert!(!re.is_match("- Copyright 2018 \n- SPDX-License-Identifier: MIT\n"));
+    }
+
+    #[test]
+    fn test_custom_tokens() {
+        let template = Template::new("Contact [department] at [contact].", Context::default())
+            .set_tokens(
+                [("department", "Legal"), ("contact", "legal@acme.com")]
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            );
+        assert_eq!(
+            "Contact Legal at legal@acme.com.",
+            template.render().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_javadoc() {
+        let context = Context {
+            ident: String::from("MIT"),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new(SPDX_SHORT_TEMPLATE, context);
+        let commenter = crate::comments::BlockComment::javadoc();
+        let re = template.outdated_license_pattern(&commenter, None).unwrap();
+        assert!(re.is_match(
+            "/**\n * Copyright 2018 \n * SPDX-License-Identifier: MIT\n */\npackage main;\n"
+        ));
+        assert!(!re.is_match("/**\n ** Copyright 2018 \n * SPDX-License-Identifier: MIT\n */\n"));
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_ml() {
+        let context = Context {
+            ident: String::from("MIT"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: String::from("Me (Corp)"),
+                ..Default::default()
+            }]),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new(SPDX_SHORT_TEMPLATE, context);
+        let commenter = crate::comments::BlockComment::new("(*\n", "*)\n");
+        for columns in [None, Some(80)].iter().copied() {
+            let re = template
+                .outdated_license_pattern(&commenter, columns)
+                .unwrap();
+            assert!(re.is_match(
+                "(*\nCopyright 2018 Me (Corp)\nSPDX-License-Identifier: MIT\n*)\nlet x = 1\n"
+            ));
+            assert!(
+                re.is_match("(*\nCopyright 2018-2019 Someone\nSPDX-License-Identifier: MIT\n*)\n")
+            );
+            assert!(!re.is_match("(*\nCopyright 2018 Me\nSPDX-License-Identifier: MIT\n*\n"));
+            assert!(!re.is_match("(\nCopyright 2018 Me\nSPDX-License-Identifier: MIT\n*)\n"));
+        }
+    }
+
+    #[test]
+    fn test_omit_year() {
+        let context = Context {
+            ident: String::from("MIT"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: String::from("Me"),
+                email: Emails::default(),
+                ..Default::default()
+            }]),
+            year: Some(String::from("2020")),
+            omit_year: true,
+            ..Default::default()
+        };
+        let template = Template::new(
+            "Copyright [year] [name of author]\n[year] All rights reserved.",
+            context,
+        );
+        assert_eq!(
+            "Copyright Me\nAll rights reserved.",
+            template.render().unwrap()
+        );
+
+        let commenter = crate::comments::LineComment::new("#");
+        let re = template.outdated_license_pattern(&commenter, None).unwrap();
+        assert!(re.is_match("# Copyright Me\n# All rights reserved."));
+        assert!(re.is_match("# Copyright 2018-2019 Someone\n# 2019 All rights reserved."));
+        assert!(!re.is_match("# CopyrightMe\n# All rights reserved."));
+    }
+
+    #[test]
+    fn test_spdx_token() {
+        let template = |spdx_id: Option<&str>| {
+            Template::new(
+                "Licensed under the [ident] (SPDX: [spdx])",
+                Context {
+                    ident: String::from("MIT License"),
+                    spdx_id: spdx_id.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+            .render()
+            .unwrap()
+        };
+        assert_eq!(
+            "Licensed under the MIT License (SPDX: MIT)",
+            template(Some("MIT"))
+        );
+        assert_eq!(
+            "Licensed under the MIT License (SPDX: MIT License)",
+            template(None)
+        );
+    }
+
+    #[test]
+    fn test_copyright_symbol() {
+        let template = |symbol: Option<&str>| {
+            Template::new(
+                "Copyright [copyright_symbol] [year] Me",
+                Context {
+                    ident: String::from("MIT"),
+                    year: Some(String::from("2020")),
+                    copyright_symbol: symbol.map(str::to_string),
+                    ..Default::default()
+                },
+            )
+        };
+        assert_eq!("Copyright (C) 2020 Me", template(None).render().unwrap());
+        assert_eq!("Copyright © 2020 Me", template(Some("©")).render().unwrap());
+
+        let commenter = crate::comments::LineComment::new("#");
+        let re = template(Some("©"))
+            .outdated_license_pattern(&commenter, None)
+            .unwrap();
+        assert!(re.is_match("# Copyright (C) 2019 Me"));
+        assert!(re.is_match("# Copyright © 2024 Me"));
+        assert!(!re.is_match("# Copyright 2019 Me"));
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_separator() {
+        let context = Context {
+            ident: String::from("MIT"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: String::from("Me"),
+                email: Emails::default(),
+                ..Default::default()
+            }]),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new(
+            "Copyright [year] [name of author]\nAll rights reserved.",
+            context,
+        );
+        let commenter = crate::comments::LineComment::new("#").set_prefix_separator("");
+        assert_eq!(
+            "#Copyright 2020 Me\n#All rights reserved.\n",
+            commenter.comment(&template.render().unwrap(), None)
+        );
+
+        let re = template.outdated_license_pattern(&commenter, None).unwrap();
+        assert!(re.is_match("#Copyright 2019 Someone\n#All rights reserved."));
+        assert!(!re.is_match("# Copyright 2019 Someone\n# All rights reserved."));
+    }
+
+    #[test]
+    fn test_preserve_line_breaks() {
+        let context = Context {
+            ident: String::from("GPL-3.0"),
+            year: Some(String::from("2020")),
+            unwrap_text: true,
+            preserve_line_breaks: true,
+            ..Default::default()
+        };
+        let text = "Copyright [year]\nThis program is free software: you can\nredistribute it under the terms of the GPL.\n\nSee the\nGNU General Public License.";
+        let template = Template::new(text, context);
+        let rendered = template.render().unwrap();
+        assert_eq!(text.replace("[year]", "2020"), rendered);
+
+        let commenter = crate::comments::LineComment::new("#");
+        let columns = template.columns(Some(20));
+        assert_eq!(None, columns);
+        assert_eq!(
+            "# Copyright 2020\n# This program is free software: you can\n# redistribute it under the terms of the GPL.\n#\n# See the\n# GNU General Public License.\n",
+            commenter.comment(&rendered, columns)
+        );
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_other_columns() {
+        let context = |year: &str| Context {
+            ident: String::from("AGPL-3.0"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: String::from("Mathew Robinson"),
+                email: Emails::default(),
+                ..Default::default()
+            }]),
+            year: Some(String::from(year)),
+            ..Default::default()
+        };
+        let text = "Copyright (C) [year] [name of author] This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General Public License as published by the Free Software Foundation, version 3.\n\nThis program is distributed in the hope that it will be useful.";
+        let commenters: Vec<Box<dyn Comment>> = vec![
+            Box::new(crate::comments::LineComment::new("#")),
+            Box::new(crate::comments::BlockComment::javadoc()),
+        ];
+
+        for commenter in commenters {
+            let old = commenter.comment(
+                &Template::new(text, context("2019")).render().unwrap(),
+                Some(80),
+            );
+            let current = Template::new(text, context("2020"));
+            let re = current
+                .outdated_license_pattern(commenter.as_ref(), Some(100))
+                .unwrap();
+            assert!(re.is_match(&old), "{} does not match:\n{}", re, old);
+
+            let rewrapped = commenter.comment(&current.render().unwrap(), Some(100));
+            assert!(
+                re.is_match(&rewrapped),
+                "{} does not match:\n{}",
+                re,
+                rewrapped
+            );
+
+            // Paragraphs must still be separated the same way.
+            let joined = old.replacen("\n#\n", "\n", 1).replacen("\n *\n", "\n", 1);
+            assert!(!re.is_match(&joined));
+        }
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_rules() {
+        let context = Context {
+            ident: String::from("MIT"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: String::from("Mathew Robinson"),
+                email: Emails::default(),
+                ..Default::default()
+            }]),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let commenter =
+            crate::comments::RuledComment::new(Box::new(crate::comments::LineComment::new("#")))
+                .set_prefix_line(Some("="))
+                .set_suffix_line(Some("-"));
+        let re = Template::new("Copyright [year] [name of author]", context)
+            .outdated_license_pattern(&commenter, None)
+            .unwrap();
+        assert!(re.is_match("# =====\n# Copyright 2019 Me\n# -----"));
+        assert!(!re.is_match("# Copyright 2019 Me"));
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_padding() {
+        let context = Context {
+            ident: String::from("MIT"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: String::from("Mathew Robinson"),
+                ..Default::default()
+            }]),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new(
+            "Copyright [year] [name of author]\nMIT licensed.\n",
+            context,
+        );
+        let line = crate::comments::PaddedComment::new(
+            Box::new(crate::comments::LineComment::new("#")),
+            1,
+        );
+        let block = crate::comments::PaddedComment::new(
+            Box::new(crate::comments::BlockComment::javadoc()),
+            1,
+        );
+        for columns in [None, Some(80)].iter().copied() {
+            let re = template.outdated_license_pattern(&line, columns).unwrap();
+            assert!(re.is_match("#\n# Copyright 2019 Me\n# MIT licensed.\n#\n"));
+            assert!(!re.is_match("# Copyright 2019 Me\n# MIT licensed.\n"));
+
+            let re = template.outdated_license_pattern(&block, columns).unwrap();
+            assert!(re.is_match("/**\n *\n * Copyright 2019 Me\n * MIT licensed.\n *\n */\n"));
+            assert!(!re.is_match("/**\n * Copyright 2019 Me\n * MIT licensed.\n */\n"));
+        }
+    }
+
+    #[test]
+    fn test_flexible_runs() {
+        assert_eq!(r"\#\ ={3,}", flexible_runs(r"\#\ ====="));
+        assert_eq!(r"\-{3,}x\-\-", flexible_runs(r"\-\-\-\-x\-\-"));
+        assert_eq!("aaaa", flexible_runs("aaaa"));
+    }
+
+    #[test]
+    fn test_outdated_license_pattern_batch() {
+        let context = Context {
+            ident: String::from("MIT"),
+            authors: Authors::from(vec![CopyrightHolder {
+                name: String::from("Me"),
+                ..Default::default()
+            }]),
+            year: Some(String::from("2020")),
+            ..Default::default()
+        };
+        let template = Template::new(SPDX_SHORT_TEMPLATE, context);
+        let commenter = crate::comments::LineComment::new("REM");
+        for columns in [None, Some(80)].iter().copied() {
+            let re = template
+                .outdated_license_pattern(&commenter, columns)
+                .unwrap();
+            assert!(re.is_match("REM Copyright 2018 Me\nREM SPDX-License-Identifier: MIT\n"));
+            assert!(re.is_match("rem Copyright 2018 Me\r\nRem SPDX-License-Identifier: MIT\r\n"));
+            assert!(!re.is_match("rem copyright 2018 Me\nrem SPDX-License-Identifier: MIT\n"));
+        }
     }
 }